@@ -1,6 +1,15 @@
-use std::path::PathBuf;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, mpsc};
+use std::thread;
 
-use crate::{ExtractionError, ExtractionOutcome, ExtractionWorkflow};
+use crate::workflow::absolute_path;
+use crate::{
+    ArchiveCreationError, ArchiveCreationOutcome, ArchiveCreationWorkflow, ExtractionError,
+    ExtractionOutcome, ExtractionWorkflow, FolderWatcher, PasswordPrompt, PasswordResponse,
+    VerificationReport, WatchError,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileOutcome {
@@ -8,18 +17,71 @@ pub struct FileOutcome {
     pub result: Result<ExtractionOutcome, ExtractionError>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedFile {
+    pub input: PathBuf,
+    pub result: Result<VerificationReport, ExtractionError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedArchive {
+    pub input: PathBuf,
+    pub result: Result<ArchiveCreationOutcome, ArchiveCreationError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandedDirectory {
+    pub archives: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryExpansion {
+    pub directory: PathBuf,
+    pub result: Result<ExpandedDirectory, ExtractionError>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BatchReport {
     pub files: Vec<FileOutcome>,
+    pub created_archives: Vec<CreatedArchive>,
+    pub expanded_directories: Vec<DirectoryExpansion>,
+}
+
+pub enum DirectoryHandling {
+    ExtractArchives { recursive: bool },
+    CreateArchive(ArchiveCreationWorkflow),
+}
+
+impl Default for DirectoryHandling {
+    fn default() -> Self {
+        Self::ExtractArchives { recursive: false }
+    }
 }
 
 pub struct DesktopApplication {
     workflow: ExtractionWorkflow,
+    directory_handling: DirectoryHandling,
+    concurrency: usize,
 }
 
 impl DesktopApplication {
     pub fn new(workflow: ExtractionWorkflow) -> Self {
-        Self { workflow }
+        Self {
+            workflow,
+            directory_handling: DirectoryHandling::default(),
+            concurrency: 1,
+        }
+    }
+
+    pub fn with_directory_handling(mut self, directory_handling: DirectoryHandling) -> Self {
+        self.directory_handling = directory_handling;
+        self
+    }
+
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
     }
 
     pub fn process_files<I, P>(&self, inputs: I) -> BatchReport
@@ -27,15 +89,323 @@ impl DesktopApplication {
         I: IntoIterator<Item = P>,
         P: Into<PathBuf>,
     {
-        let files = inputs
+        let mut report = BatchReport {
+            files: Vec::new(),
+            created_archives: Vec::new(),
+            expanded_directories: Vec::new(),
+        };
+        let mut extraction_inputs = Vec::new();
+        for input in inputs {
+            let input = input.into();
+            if !input.is_dir() {
+                extraction_inputs.push(input);
+                continue;
+            }
+
+            if let DirectoryHandling::CreateArchive(archive_creation) = &self.directory_handling {
+                let result = archive_creation.create([&input]);
+                report
+                    .created_archives
+                    .push(CreatedArchive { input, result });
+                continue;
+            }
+
+            let result = self.expand_directory(&input);
+            if let Ok(expanded) = &result {
+                extraction_inputs.extend(expanded.archives.iter().cloned());
+            }
+            report.expanded_directories.push(DirectoryExpansion {
+                directory: input,
+                result,
+            });
+        }
+
+        report.files = self.extract_all(extraction_inputs);
+        report
+    }
+
+    pub fn verify_files<I, P>(&self, inputs: I) -> Vec<VerifiedFile>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let mut seen = HashSet::new();
+        let mut verified = Vec::new();
+        for input in inputs {
+            let input = input.into();
+            if !seen.insert(self.workflow.archive_set_key(&input)) {
+                continue;
+            }
+            let result = self.workflow.verify(&input);
+            verified.push(VerifiedFile { input, result });
+        }
+        verified
+    }
+
+    pub fn watch(
+        &self,
+        watcher: &mut FolderWatcher,
+        mut on_report: impl FnMut(&BatchReport),
+    ) -> Result<(), WatchError> {
+        loop {
+            let files = watcher.next_batch()?;
+            let report = self.process_watched_files(files)?;
+            watcher.ignore(report.files.iter().flat_map(|file| match &file.result {
+                Ok(outcome) => {
+                    let mut outputs = vec![outcome.output.clone()];
+                    outputs.extend(outcome.additional_outputs.iter().cloned());
+                    outputs
+                }
+                Err(_) => Vec::new(),
+            }));
+            if !report.files.is_empty() {
+                on_report(&report);
+            }
+        }
+    }
+
+    fn process_watched_files(&self, files: Vec<PathBuf>) -> Result<BatchReport, WatchError> {
+        let collected = self.collect_archives(files).map_err(WatchError::Engine)?;
+        let mut report = BatchReport {
+            files: Vec::new(),
+            created_archives: Vec::new(),
+            expanded_directories: Vec::new(),
+        };
+        report.files = self.extract_all(collected.archives);
+        Ok(report)
+    }
+
+    fn extract_all(&self, inputs: Vec<PathBuf>) -> Vec<FileOutcome> {
+        if self.concurrency == 1 || inputs.len() < 2 {
+            return inputs
+                .into_iter()
+                .map(|input| {
+                    let result = self.workflow.extract(&input);
+                    FileOutcome { input, result }
+                })
+                .collect();
+        }
+
+        let mut jobs: Vec<(PathBuf, Vec<(usize, PathBuf)>)> = Vec::new();
+        for (index, input) in inputs.into_iter().enumerate() {
+            let key = self.workflow.archive_set_key(&input);
+            match jobs.iter_mut().find(|(job_key, _)| job_key == &key) {
+                Some((_, job)) => job.push((index, input)),
+                None => jobs.push((key, vec![(index, input)])),
+            }
+        }
+        let count = jobs.iter().map(|(_, job)| job.len()).sum();
+        let workers = self.concurrency.min(jobs.len());
+        let jobs = Mutex::new(
+            jobs.into_iter()
+                .map(|(_, job)| job)
+                .collect::<VecDeque<_>>(),
+        );
+
+        let mut outcomes: Vec<Option<FileOutcome>> = (0..count).map(|_| None).collect();
+        thread::scope(|scope| {
+            let (sender, messages) = mpsc::channel();
+            for _ in 0..workers {
+                let sender = sender.clone();
+                let jobs = &jobs;
+                scope.spawn(move || {
+                    let prompt = CoordinatedPrompt {
+                        messages: sender.clone(),
+                    };
+                    while let Some(job) = next_job(jobs) {
+                        for (index, input) in job {
+                            let result = self.workflow.extract_with_prompt(&input, &prompt);
+                            let outcome = FileOutcome { input, result };
+                            if sender
+                                .send(WorkerMessage::Finished(index, outcome))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for message in messages {
+                match message {
+                    WorkerMessage::Prompt {
+                        input,
+                        previous_attempt_failed,
+                        comment,
+                        reply,
+                    } => {
+                        let response = self.workflow.password_prompt().request_password(
+                            &input,
+                            previous_attempt_failed,
+                            comment.as_deref(),
+                        );
+                        let _ = reply.send(response);
+                    }
+                    WorkerMessage::DatabasePassphrase {
+                        database,
+                        previous_attempt_failed,
+                        new_passphrase,
+                        reply,
+                    } => {
+                        let response = self.workflow.password_prompt().request_database_passphrase(
+                            &database,
+                            previous_attempt_failed,
+                            new_passphrase,
+                        );
+                        let _ = reply.send(response);
+                    }
+                    WorkerMessage::Finished(index, outcome) => outcomes[index] = Some(outcome),
+                }
+            }
+        });
+
+        outcomes
             .into_iter()
-            .map(|input| {
-                let input = input.into();
-                let result = self.workflow.extract(&input);
-                FileOutcome { input, result }
+            .map(|outcome| outcome.expect("every queued input produces an outcome"))
+            .collect()
+    }
+
+    fn expand_directory(&self, directory: &Path) -> Result<ExpandedDirectory, ExtractionError> {
+        self.collect_archives(self.directory_files(directory)?)
+    }
+
+    fn collect_archives(&self, files: Vec<PathBuf>) -> Result<ExpandedDirectory, ExtractionError> {
+        let mut expanded = ExpandedDirectory {
+            archives: Vec::new(),
+            skipped: Vec::new(),
+        };
+        let mut covered = HashSet::new();
+        for file in files {
+            let file = absolute_path(&file)?;
+            if covered.contains(&file) {
+                continue;
+            }
+            match self.workflow.probe_archive(&file) {
+                Ok(Some(sources)) => {
+                    expanded.archives.push(sources[0].clone());
+                    covered.extend(sources);
+                }
+                Ok(None) => expanded.skipped.push(file),
+                Err(
+                    error @ (ExtractionError::EngineNotFound(_)
+                    | ExtractionError::EngineLaunch { .. }),
+                ) => return Err(error),
+                Err(_) => expanded.archives.push(file),
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn directory_files(&self, directory: &Path) -> Result<Vec<PathBuf>, ExtractionError> {
+        let mut files = Vec::new();
+        let mut directories = vec![directory.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = fs::read_dir(&directory)
+                .map_err(|error| scan_error(&directory, error))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| scan_error(&directory, error))?;
+            entries.sort();
+
+            let mut nested = Vec::new();
+            for path in entries {
+                let file_type = fs::symlink_metadata(&path)
+                    .map_err(|error| scan_error(&path, error))?
+                    .file_type();
+                if file_type.is_file() {
+                    files.push(path);
+                } else if file_type.is_dir()
+                    && matches!(
+                        self.directory_handling,
+                        DirectoryHandling::ExtractArchives { recursive: true }
+                    )
+                    && !is_workspace(&path)
+                {
+                    nested.push(path);
+                }
+            }
+            directories.extend(nested.into_iter().rev());
+        }
+        Ok(files)
+    }
+}
+
+enum WorkerMessage {
+    Prompt {
+        input: PathBuf,
+        previous_attempt_failed: bool,
+        comment: Option<String>,
+        reply: mpsc::Sender<Option<PasswordResponse>>,
+    },
+    DatabasePassphrase {
+        database: PathBuf,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+        reply: mpsc::Sender<Option<String>>,
+    },
+    Finished(usize, FileOutcome),
+}
+
+struct CoordinatedPrompt {
+    messages: mpsc::Sender<WorkerMessage>,
+}
+
+impl PasswordPrompt for CoordinatedPrompt {
+    fn request_password(
+        &self,
+        input: &Path,
+        previous_attempt_failed: bool,
+        comment: Option<&str>,
+    ) -> Option<PasswordResponse> {
+        let (reply, response) = mpsc::channel();
+        self.messages
+            .send(WorkerMessage::Prompt {
+                input: input.to_path_buf(),
+                previous_attempt_failed,
+                comment: comment.map(str::to_owned),
+                reply,
+            })
+            .ok()?;
+        response.recv().ok().flatten()
+    }
+
+    fn request_database_passphrase(
+        &self,
+        database: &Path,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Option<String> {
+        let (reply, response) = mpsc::channel();
+        self.messages
+            .send(WorkerMessage::DatabasePassphrase {
+                database: database.to_path_buf(),
+                previous_attempt_failed,
+                new_passphrase,
+                reply,
             })
-            .collect();
+            .ok()?;
+        response.recv().ok().flatten()
+    }
+}
+
+fn next_job(jobs: &Mutex<VecDeque<Vec<(usize, PathBuf)>>>) -> Option<Vec<(usize, PathBuf)>> {
+    jobs.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop_front()
+}
+
+fn is_workspace(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(".ezz-work-"))
+}
 
-        BatchReport { files }
+fn scan_error(path: &Path, error: std::io::Error) -> ExtractionError {
+    ExtractionError::FileSystem {
+        operation: "scan input directory",
+        path: path.to_path_buf(),
+        message: error.to_string(),
     }
 }
@@ -0,0 +1,456 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::seven_zip::{CreateError, CreateSwitches, SevenZip};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    SevenZip,
+    Zip,
+    TarZstd,
+}
+
+impl ArchiveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::SevenZip => "7z",
+            Self::Zip => "zip",
+            Self::TarZstd => "tar.zst",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::SevenZip => "7z",
+            Self::Zip => "ZIP",
+            Self::TarZstd => "tar.zst",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveCreationOptions {
+    pub format: ArchiveFormat,
+    pub password: Option<String>,
+    pub encrypt_headers: bool,
+    pub volume_size: Option<u64>,
+}
+
+impl Default for ArchiveCreationOptions {
+    fn default() -> Self {
+        Self {
+            format: ArchiveFormat::SevenZip,
+            password: None,
+            encrypt_headers: false,
+            volume_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveCreationOutcome {
+    pub inputs: Vec<PathBuf>,
+    pub output: PathBuf,
+    pub volumes: Vec<PathBuf>,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ArchiveCreationError {
+    #[error("No inputs were given to archive")]
+    NoInputs,
+
+    #[error("Input does not exist: {0}")]
+    InputNotFound(PathBuf),
+
+    #[error("Inputs must share one parent directory: {0}")]
+    MixedParents(PathBuf),
+
+    #[error("7-Zip executable does not exist: {0}")]
+    EngineNotFound(PathBuf),
+
+    #[error("Could not start 7-Zip at {path}: {message}")]
+    EngineLaunch { path: PathBuf, message: String },
+
+    #[error("7-Zip failed to {operation} with exit code {exit_code:?}: {message}")]
+    EngineFailed {
+        operation: &'static str,
+        exit_code: Option<i32>,
+        message: String,
+    },
+
+    #[error("{format} archives do not support {option}")]
+    UnsupportedOption {
+        format: &'static str,
+        option: &'static str,
+    },
+
+    #[error("Could not {operation} {path}: {message}")]
+    FileSystem {
+        operation: &'static str,
+        path: PathBuf,
+        message: String,
+    },
+}
+
+pub struct ArchiveCreationWorkflow {
+    seven_zip: PathBuf,
+    options: ArchiveCreationOptions,
+}
+
+impl ArchiveCreationWorkflow {
+    pub fn new(seven_zip: impl Into<PathBuf>) -> Self {
+        Self::with_options(seven_zip, ArchiveCreationOptions::default())
+    }
+
+    pub fn with_options(seven_zip: impl Into<PathBuf>, options: ArchiveCreationOptions) -> Self {
+        Self {
+            seven_zip: seven_zip.into(),
+            options,
+        }
+    }
+
+    pub fn create<I, P>(&self, inputs: I) -> Result<ArchiveCreationOutcome, ArchiveCreationError>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let inputs = inputs
+            .into_iter()
+            .map(|input| absolute_path(&input.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let first = inputs.first().ok_or(ArchiveCreationError::NoInputs)?;
+        for input in &inputs {
+            if fs::symlink_metadata(input).is_err() {
+                return Err(ArchiveCreationError::InputNotFound(input.clone()));
+            }
+        }
+        let parent = first
+            .parent()
+            .ok_or_else(|| ArchiveCreationError::MixedParents(first.clone()))?;
+        if let Some(input) = inputs.iter().find(|input| input.parent() != Some(parent)) {
+            return Err(ArchiveCreationError::MixedParents(input.clone()));
+        }
+        self.validate_options()?;
+        if !self.seven_zip.is_file() {
+            return Err(ArchiveCreationError::EngineNotFound(self.seven_zip.clone()));
+        }
+
+        let names: Vec<&OsStr> = inputs
+            .iter()
+            .map(|input| input.file_name().expect("absolute input file name"))
+            .collect();
+        let stem = archive_stem(&inputs, parent);
+        let workspace = tempfile::Builder::new()
+            .prefix(".ezz-work-")
+            .tempdir_in(parent)
+            .map_err(|error| file_system_error("create workspace for", first, error))?;
+        let volumes = self.pack(parent, &names, workspace.path())?;
+        let split = self.options.volume_size.is_some();
+        let committed = commit_volumes(parent, &stem, self.options.format, &volumes, split)?;
+
+        Ok(ArchiveCreationOutcome {
+            inputs,
+            output: committed[0].clone(),
+            volumes: if split { committed } else { Vec::new() },
+        })
+    }
+
+    fn validate_options(&self) -> Result<(), ArchiveCreationError> {
+        let format = self.options.format;
+        let unsupported = |option| ArchiveCreationError::UnsupportedOption {
+            format: format.name(),
+            option,
+        };
+        if format == ArchiveFormat::TarZstd && self.options.password.is_some() {
+            return Err(unsupported("passwords"));
+        }
+        if self.options.encrypt_headers && format != ArchiveFormat::SevenZip {
+            return Err(unsupported("header encryption"));
+        }
+        if self.options.encrypt_headers && self.options.password.is_none() {
+            return Err(unsupported("header encryption without a password"));
+        }
+        if self.options.volume_size == Some(0) {
+            return Err(unsupported("empty volumes"));
+        }
+        Ok(())
+    }
+
+    fn pack(
+        &self,
+        parent: &Path,
+        names: &[&OsStr],
+        workspace: &Path,
+    ) -> Result<Vec<PathBuf>, ArchiveCreationError> {
+        let seven_zip = SevenZip::new(&self.seven_zip);
+        let archive = workspace.join(format!("archive.{}", self.options.format.extension()));
+        let switches = CreateSwitches {
+            archive_type: "7z",
+            password: self.options.password.as_deref(),
+            encrypt_headers: self.options.encrypt_headers,
+            volume_size: self.options.volume_size,
+        };
+        match self.options.format {
+            ArchiveFormat::SevenZip => seven_zip.create(parent, names, &archive, &switches)?,
+            ArchiveFormat::Zip => seven_zip.create(
+                parent,
+                names,
+                &archive,
+                &CreateSwitches {
+                    archive_type: "zip",
+                    ..switches
+                },
+            )?,
+            ArchiveFormat::TarZstd => {
+                let tar = workspace.join("archive.tar");
+                seven_zip.create(
+                    parent,
+                    names,
+                    &tar,
+                    &CreateSwitches {
+                        archive_type: "tar",
+                        volume_size: None,
+                        ..switches
+                    },
+                )?;
+                seven_zip.create(
+                    workspace,
+                    &[OsStr::new("archive.tar")],
+                    &archive,
+                    &CreateSwitches {
+                        archive_type: "zstd",
+                        ..switches
+                    },
+                )?;
+                fs::remove_file(&tar)
+                    .map_err(|error| file_system_error("remove intermediate tar", &tar, error))?;
+            }
+        }
+
+        if self.options.volume_size.is_none() {
+            return Ok(vec![archive]);
+        }
+        let mut volumes = Vec::new();
+        for sequence in 1_u32.. {
+            let mut volume = archive.clone().into_os_string();
+            volume.push(format!(".{sequence:03}"));
+            let volume = PathBuf::from(volume);
+            if !volume.is_file() {
+                break;
+            }
+            volumes.push(volume);
+        }
+        if volumes.is_empty() {
+            return Err(ArchiveCreationError::EngineFailed {
+                operation: "create volumes for",
+                exit_code: Some(0),
+                message: "7-Zip did not produce any volume".to_owned(),
+            });
+        }
+        Ok(volumes)
+    }
+}
+
+fn archive_stem(inputs: &[PathBuf], parent: &Path) -> OsString {
+    let source = match inputs {
+        [input] if input.is_dir() => input.file_name(),
+        [input] => input.file_stem(),
+        _ => parent.file_name(),
+    };
+    source
+        .unwrap_or_else(|| OsStr::new("archive"))
+        .to_os_string()
+}
+
+fn commit_volumes(
+    parent: &Path,
+    stem: &OsStr,
+    format: ArchiveFormat,
+    volumes: &[PathBuf],
+    split: bool,
+) -> Result<Vec<PathBuf>, ArchiveCreationError> {
+    let targets = unique_archive_destination(parent, stem, format, volumes, split);
+    let mut committed: Vec<(&Path, &Path)> = Vec::new();
+    for (volume, target) in volumes.iter().zip(&targets) {
+        if let Err(error) = fs::rename(volume, target) {
+            for (volume, target) in committed {
+                let _ = fs::rename(target, volume);
+            }
+            return Err(file_system_error(
+                "commit created archive to",
+                target,
+                error,
+            ));
+        }
+        committed.push((volume, target));
+    }
+    Ok(targets)
+}
+
+fn unique_archive_destination(
+    parent: &Path,
+    stem: &OsStr,
+    format: ArchiveFormat,
+    volumes: &[PathBuf],
+    split: bool,
+) -> Vec<PathBuf> {
+    for sequence in 0_u64.. {
+        let mut base = OsString::from(stem);
+        if sequence > 0 {
+            base.push(format!(" ({sequence})"));
+        }
+        base.push(".");
+        base.push(format.extension());
+
+        let candidates: Vec<PathBuf> = volumes
+            .iter()
+            .map(|volume| {
+                let mut name = base.clone();
+                if split && let Some(extension) = volume.extension() {
+                    name.push(".");
+                    name.push(extension);
+                }
+                parent.join(name)
+            })
+            .collect();
+        if candidates.iter().all(|candidate| !candidate.exists()) {
+            return candidates;
+        }
+    }
+
+    unreachable!("u64 destination sequence exhausted")
+}
+
+fn absolute_path(path: &Path) -> Result<PathBuf, ArchiveCreationError> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    std::env::current_dir()
+        .map(|current| current.join(path))
+        .map_err(|error| file_system_error("resolve absolute path for", path, error))
+}
+
+impl From<CreateError> for ArchiveCreationError {
+    fn from(error: CreateError) -> Self {
+        match error {
+            CreateError::Launch { path, message } => Self::EngineLaunch { path, message },
+            CreateError::Failed { exit_code, message } => Self::EngineFailed {
+                operation: "create",
+                exit_code,
+                message,
+            },
+        }
+    }
+}
+
+fn file_system_error(
+    operation: &'static str,
+    path: &Path,
+    error: std::io::Error,
+) -> ArchiveCreationError {
+    ArchiveCreationError::FileSystem {
+        operation,
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractionWorkflow;
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn directory_is_packed_next_to_itself_without_overwriting() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let folder = sandbox.path().join("photos");
+        let existing = sandbox.path().join("photos.7z");
+        std::fs::create_dir(&folder).expect("create input directory");
+        std::fs::write(folder.join("first.txt"), b"first").expect("create first file");
+        std::fs::write(folder.join("second.txt"), b"second").expect("create second file");
+        std::fs::write(&existing, b"existing").expect("create conflicting archive");
+
+        let outcome = ArchiveCreationWorkflow::new(&seven_zip)
+            .create([&folder])
+            .expect("create archive");
+
+        let output = sandbox.path().join("photos (1).7z");
+        assert_eq!(outcome.output, output);
+        assert!(outcome.volumes.is_empty());
+        assert_eq!(std::fs::read(&existing).unwrap(), b"existing");
+        assert!(folder.is_dir(), "archive creation must keep its inputs");
+        assert_eq!(
+            std::fs::read_dir(sandbox.path()).unwrap().count(),
+            3,
+            "archive creation must not leave a workspace"
+        );
+
+        std::fs::remove_dir_all(&folder).expect("remove original directory");
+        ExtractionWorkflow::new(&seven_zip)
+            .extract(&output)
+            .expect("extract created archive");
+        assert_eq!(std::fs::read(folder.join("first.txt")).unwrap(), b"first");
+        assert_eq!(std::fs::read(folder.join("second.txt")).unwrap(), b"second");
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn encrypted_split_archive_commits_every_volume() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("payload.bin");
+        let incompressible: Vec<u8> = (0_u32..8 * 1024)
+            .map(|index| (index.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        std::fs::write(&payload, incompressible).expect("create payload");
+
+        let outcome = ArchiveCreationWorkflow::with_options(
+            &seven_zip,
+            ArchiveCreationOptions {
+                format: ArchiveFormat::SevenZip,
+                password: Some("volume password".to_owned()),
+                encrypt_headers: true,
+                volume_size: Some(1024),
+            },
+        )
+        .create([&payload])
+        .expect("create encrypted split archive");
+
+        assert_eq!(outcome.output, sandbox.path().join("payload.7z.001"));
+        assert!(
+            outcome.volumes.len() > 1,
+            "payload must span several volumes"
+        );
+        assert!(outcome.volumes.iter().all(|volume| volume.is_file()));
+        assert_eq!(outcome.volumes[1], sandbox.path().join("payload.7z.002"));
+    }
+
+    fn prepared_seven_zip() -> PathBuf {
+        let binary_name = if cfg!(target_os = "windows") {
+            "7zz.exe"
+        } else {
+            "7zz"
+        };
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("ezz-tools")
+            .join("26.02")
+            .join(binary_name)
+    }
+}
@@ -0,0 +1,374 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CarrierFormat {
+    Mp4,
+    Matroska,
+    Avi,
+    Jpeg,
+    Png,
+    Gif,
+    Pdf,
+}
+
+impl CarrierFormat {
+    pub(crate) fn detect(path: &Path) -> io::Result<Option<Self>> {
+        let mut header = [0_u8; 12];
+        let length = read_prefix(&mut File::open(path)?, &mut header)?;
+        let header = &header[..length];
+
+        let format = if header.len() >= 8 && &header[4..8] == b"ftyp" {
+            Some(Self::Mp4)
+        } else if header.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+            Some(Self::Matroska)
+        } else if header.len() >= 12 && header.starts_with(b"RIFF") && &header[8..12] == b"AVI " {
+            Some(Self::Avi)
+        } else if header.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if header.starts_with(b"%PDF-") {
+            Some(Self::Pdf)
+        } else {
+            None
+        };
+        Ok(format)
+    }
+
+    pub(crate) fn end(self, path: &Path, limit: u64) -> io::Result<Option<u64>> {
+        let file = File::open(path)?;
+        match self {
+            Self::Mp4 => mp4_end(file, limit),
+            Self::Matroska => matroska_end(file, limit),
+            Self::Avi => avi_end(file, limit),
+            Self::Jpeg => jpeg_end(BufReader::new(file).take(limit)),
+            Self::Png => png_end(file, limit),
+            Self::Gif => gif_end(BufReader::new(file).take(limit)),
+            Self::Pdf => pdf_end(BufReader::new(file).take(limit)),
+        }
+    }
+}
+
+fn read_prefix(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn mp4_end(mut file: File, limit: u64) -> io::Result<Option<u64>> {
+    let mut position = 0;
+    while position < limit {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0_u8; 16];
+        let length = read_prefix(&mut file, &mut header)?;
+        if length < 8 || !header[4..8].iter().all(u8::is_ascii_graphic) {
+            break;
+        }
+        let size = match u32::from_be_bytes(header[..4].try_into().expect("box size")) {
+            0 => return Ok(None),
+            1 if length == 16 => u64::from_be_bytes(header[8..16].try_into().expect("box size")),
+            1 => break,
+            size => u64::from(size),
+        };
+        if size < 8 {
+            break;
+        }
+        position = position.saturating_add(size);
+    }
+    Ok((position <= limit).then_some(position))
+}
+
+fn matroska_end(mut file: File, limit: u64) -> io::Result<Option<u64>> {
+    let mut position = 0;
+    while position < limit {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0_u8; 12];
+        let length = read_prefix(&mut file, &mut header)?;
+        let Some((_, id_length)) = ebml_vint(&header[..length]) else {
+            break;
+        };
+        let Some((size, size_length)) = ebml_vint(&header[id_length..length]) else {
+            break;
+        };
+        if size == (1 << (7 * size_length)) - 1 {
+            return Ok(None);
+        }
+        position = position.saturating_add((id_length + size_length) as u64 + size);
+    }
+    Ok((position <= limit).then_some(position))
+}
+
+fn ebml_vint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let first = *bytes.first()?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 || bytes.len() < length {
+        return None;
+    }
+    let mut value = u64::from(first) & ((1 << (8 - length)) - 1);
+    for byte in &bytes[1..length] {
+        value = (value << 8) | u64::from(*byte);
+    }
+    Some((value, length))
+}
+
+fn avi_end(mut file: File, limit: u64) -> io::Result<Option<u64>> {
+    let mut position = 0;
+    while position < limit {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0_u8; 8];
+        if read_prefix(&mut file, &mut header)? < 8 || &header[..4] != b"RIFF" {
+            break;
+        }
+        let size = u64::from(u32::from_le_bytes(
+            header[4..].try_into().expect("chunk size"),
+        ));
+        position = position.saturating_add(8 + size + size % 2);
+    }
+    Ok((position > 0 && position <= limit).then_some(position))
+}
+
+fn png_end(mut file: File, limit: u64) -> io::Result<Option<u64>> {
+    let mut position = 8;
+    while position < limit {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0_u8; 8];
+        if read_prefix(&mut file, &mut header)? < 8 {
+            break;
+        }
+        let length = u64::from(u32::from_be_bytes(
+            header[..4].try_into().expect("chunk length"),
+        ));
+        position = position.saturating_add(12 + length);
+        if &header[4..] == b"IEND" {
+            return Ok((position <= limit).then_some(position));
+        }
+    }
+    Ok(None)
+}
+
+fn jpeg_end(mut reader: impl BufRead) -> io::Result<Option<u64>> {
+    let mut position = 0;
+    if !skip(&mut reader, 2, &mut position)? {
+        return Ok(None);
+    }
+    loop {
+        let Some(marker) = next_jpeg_marker(&mut reader, &mut position)? else {
+            return Ok(None);
+        };
+        match marker {
+            0xd9 => return Ok(Some(position)),
+            0x01 | 0xd0..=0xd7 => {}
+            _ => {
+                let mut length = [0_u8; 2];
+                if read_prefix(&mut reader, &mut length)? < 2 {
+                    return Ok(None);
+                }
+                let length = u16::from_be_bytes(length);
+                if length < 2 {
+                    return Ok(None);
+                }
+                position += 2;
+                if !skip(&mut reader, u64::from(length) - 2, &mut position)? {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+fn next_jpeg_marker(reader: &mut impl BufRead, position: &mut u64) -> io::Result<Option<u8>> {
+    let mut previous_was_marker = false;
+    loop {
+        let mut byte = [0_u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        *position += 1;
+        match (previous_was_marker, byte[0]) {
+            (_, 0xff) => previous_was_marker = true,
+            (true, 0x00) => previous_was_marker = false,
+            (true, marker) => return Ok(Some(marker)),
+            (false, _) => {}
+        }
+    }
+}
+
+fn gif_end(mut reader: impl BufRead) -> io::Result<Option<u64>> {
+    let mut header = [0_u8; 13];
+    if read_prefix(&mut reader, &mut header)? < 13 {
+        return Ok(None);
+    }
+    let mut position = 13;
+    if !skip(&mut reader, color_table_length(header[10]), &mut position)? {
+        return Ok(None);
+    }
+
+    loop {
+        let mut introducer = [0_u8; 1];
+        if reader.read(&mut introducer)? == 0 {
+            return Ok(None);
+        }
+        position += 1;
+        match introducer[0] {
+            0x3b => return Ok(Some(position)),
+            0x21 => {
+                if !skip(&mut reader, 1, &mut position)?
+                    || !skip_gif_sub_blocks(&mut reader, &mut position)?
+                {
+                    return Ok(None);
+                }
+            }
+            0x2c => {
+                let mut descriptor = [0_u8; 9];
+                if read_prefix(&mut reader, &mut descriptor)? < 9 {
+                    return Ok(None);
+                }
+                position += 9;
+                if !skip(
+                    &mut reader,
+                    color_table_length(descriptor[8]) + 1,
+                    &mut position,
+                )? || !skip_gif_sub_blocks(&mut reader, &mut position)?
+                {
+                    return Ok(None);
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+}
+
+fn color_table_length(flags: u8) -> u64 {
+    if flags & 0x80 == 0 {
+        0
+    } else {
+        3 << ((flags & 0x07) + 1)
+    }
+}
+
+fn skip_gif_sub_blocks(reader: &mut impl BufRead, position: &mut u64) -> io::Result<bool> {
+    loop {
+        let mut size = [0_u8; 1];
+        if reader.read(&mut size)? == 0 {
+            return Ok(false);
+        }
+        *position += 1;
+        if size[0] == 0 {
+            return Ok(true);
+        }
+        if !skip(reader, u64::from(size[0]), position)? {
+            return Ok(false);
+        }
+    }
+}
+
+fn pdf_end(mut reader: impl BufRead) -> io::Result<Option<u64>> {
+    const MARKER: &[u8] = b"%%EOF";
+    let mut end = None;
+    let mut position = 0_u64;
+    let mut matched = 0;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(end);
+        }
+        for byte in buffer {
+            position += 1;
+            matched = if *byte == MARKER[matched] {
+                matched + 1
+            } else if *byte == MARKER[0] {
+                1
+            } else {
+                0
+            };
+            if matched == MARKER.len() {
+                end = Some(position);
+                matched = 0;
+            }
+        }
+        let consumed = buffer.len();
+        reader.consume(consumed);
+    }
+}
+
+fn skip(reader: &mut impl Read, count: u64, position: &mut u64) -> io::Result<bool> {
+    let skipped = io::copy(&mut reader.take(count), &mut io::sink())?;
+    *position += skipped;
+    Ok(skipped == count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_carrier(bytes: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().expect("create carrier file");
+        std::io::Write::write_all(&mut file, bytes).expect("write carrier file");
+        file
+    }
+
+    #[test]
+    fn jpeg_end_is_found_after_the_end_of_image_marker() {
+        let image = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("whatever.jpg");
+        let length = std::fs::metadata(&image).unwrap().len();
+
+        assert_eq!(
+            CarrierFormat::detect(&image).unwrap(),
+            Some(CarrierFormat::Jpeg)
+        );
+        assert_eq!(
+            CarrierFormat::Jpeg.end(&image, length).unwrap(),
+            Some(length)
+        );
+        assert_eq!(CarrierFormat::Jpeg.end(&image, length - 1).unwrap(), None);
+    }
+
+    #[test]
+    fn mp4_boxes_must_end_before_the_appended_data() {
+        let mut bytes = vec![0, 0, 0, 16, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm'];
+        bytes.extend([0, 0, 2, 0, 0, 0, 0, 8, b'f', b'r', b'e', b'e']);
+        bytes.extend(b"PK\x03\x04");
+        let carrier = write_carrier(&bytes);
+
+        assert_eq!(
+            CarrierFormat::detect(carrier.path()).unwrap(),
+            Some(CarrierFormat::Mp4)
+        );
+        assert_eq!(
+            CarrierFormat::Mp4.end(carrier.path(), 24).unwrap(),
+            Some(24)
+        );
+        assert_eq!(CarrierFormat::Mp4.end(carrier.path(), 20).unwrap(), None);
+    }
+
+    #[test]
+    fn png_without_an_end_chunk_before_the_appended_data_is_rejected() {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend([0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+        let carrier = write_carrier(&bytes);
+
+        assert_eq!(
+            CarrierFormat::Png.end(carrier.path(), 20).unwrap(),
+            Some(20)
+        );
+        assert_eq!(CarrierFormat::Png.end(carrier.path(), 19).unwrap(), None);
+    }
+
+    #[test]
+    fn unknown_content_is_not_a_carrier() {
+        let carrier = write_carrier(b"PK\x03\x04 not a carrier");
+
+        assert_eq!(CarrierFormat::detect(carrier.path()).unwrap(), None);
+    }
+}
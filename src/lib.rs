@@ -5,12 +5,40 @@
 compile_error!("ezz v3 only supports Windows and macOS");
 
 mod application;
+mod archive_creation;
+mod carrier;
+mod password_hints;
+mod password_providers;
 mod password_store;
 mod seven_zip;
+mod source_cleaner;
+mod tar_metadata;
+mod watch;
 mod workflow;
 
-pub use application::{BatchReport, DesktopApplication, FileOutcome};
+pub use application::{
+    BatchReport, CreatedArchive, DesktopApplication, DirectoryExpansion, DirectoryHandling,
+    ExpandedDirectory, FileOutcome, VerifiedFile,
+};
+pub use archive_creation::{
+    ArchiveCreationError, ArchiveCreationOptions, ArchiveCreationOutcome, ArchiveCreationWorkflow,
+    ArchiveFormat,
+};
+pub use password_hints::PasswordHints;
+pub use password_providers::{
+    CommandPasswords, DirectoryPasswordFile, EnvironmentPasswords, PasswordCandidate,
+    PasswordCandidateProvider, PasswordRequest, StoredPasswords,
+};
+pub use password_store::{
+    PasswordDatabaseError, PasswordDatabaseKey, PasswordManager, PasswordRetention, StoredPassword,
+};
+pub use seven_zip::{ArchiveEntry, ArchiveListing, ArchiveProperties};
+pub use source_cleaner::{
+    DeleteCleaner, KeepCleaner, MoveToFolderCleaner, SourceCleaner, TrashCleaner,
+};
+pub use watch::{FolderWatcher, WatchError};
 pub use workflow::{
-    ExtractionError, ExtractionOutcome, ExtractionWarning, ExtractionWorkflow, PasswordPrompt,
-    PasswordResponse,
+    ArchiveSet, EntrySelection, ExtractionError, ExtractionOutcome, ExtractionPlan,
+    ExtractionWarning, ExtractionWorkflow, ExtractionWorkflowBuilder, InputFormat, PasswordPrompt,
+    PasswordResponse, PlannedLayout, PlannedOutput, VerificationProblem, VerificationReport,
 };
@@ -0,0 +1,213 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use crate::ExtractionError;
+use crate::password_providers::{PasswordCandidate, PasswordCandidateProvider, PasswordRequest};
+
+const MAX_SIDECAR_SIZE: u64 = 4096;
+const HINT_TERMINATORS: &[char] = &[']', '】', ')', '）', '}', '」', '』', '>', '》'];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHints {
+    filename_patterns: Vec<FilenamePattern>,
+    sidecar_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FilenamePattern {
+    prefix: String,
+    suffix: Option<String>,
+}
+
+impl PasswordHints {
+    pub fn new() -> Self {
+        Self {
+            filename_patterns: Vec::new(),
+            sidecar_files: Vec::new(),
+        }
+    }
+
+    pub fn with_filename_pattern(
+        mut self,
+        prefix: impl Into<String>,
+        suffix: Option<&str>,
+    ) -> Self {
+        self.filename_patterns.push(FilenamePattern {
+            prefix: prefix.into(),
+            suffix: suffix.map(str::to_owned),
+        });
+        self
+    }
+
+    pub fn with_sidecar_file(mut self, name: impl Into<String>) -> Self {
+        self.sidecar_files.push(name.into());
+        self
+    }
+
+    fn hints(&self, archive_name: &OsStr, directory: &Path) -> Vec<String> {
+        let mut candidates = self.matches(&archive_name.to_string_lossy());
+        for contents in self.sidecar_contents(directory) {
+            for line in contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+            {
+                let matches = self.matches(line);
+                if matches.is_empty() {
+                    candidates.push(line.to_owned());
+                } else {
+                    candidates.extend(matches);
+                }
+            }
+        }
+
+        let mut unique = Vec::new();
+        for candidate in candidates {
+            if !unique.contains(&candidate) {
+                unique.push(candidate);
+            }
+        }
+        unique
+    }
+
+    fn matches(&self, text: &str) -> Vec<String> {
+        let folded = text.to_ascii_lowercase();
+        let mut matches = Vec::new();
+        for pattern in &self.filename_patterns {
+            let prefix = pattern.prefix.to_ascii_lowercase();
+            if prefix.is_empty() {
+                continue;
+            }
+            for (start, _) in folded.match_indices(&prefix) {
+                let rest = &text[start + prefix.len()..];
+                let value = match &pattern.suffix {
+                    Some(suffix) => match rest.find(suffix.as_str()) {
+                        Some(end) => &rest[..end],
+                        None => continue,
+                    },
+                    None => rest
+                        .split(|character: char| {
+                            character.is_whitespace() || HINT_TERMINATORS.contains(&character)
+                        })
+                        .next()
+                        .unwrap_or_default(),
+                };
+                let value = value.trim();
+                if !value.is_empty() {
+                    matches.push(value.to_owned());
+                }
+            }
+        }
+        matches
+    }
+
+    fn sidecar_contents(&self, directory: &Path) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(directory) else {
+            return Vec::new();
+        };
+        let mut sidecars: Vec<_> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_owned();
+                let rank = self
+                    .sidecar_files
+                    .iter()
+                    .position(|sidecar| sidecar.eq_ignore_ascii_case(&name))?;
+                let metadata = entry.metadata().ok()?;
+                (metadata.is_file() && metadata.len() <= MAX_SIDECAR_SIZE)
+                    .then(|| (rank, entry.path()))
+            })
+            .collect();
+        sidecars.sort();
+        sidecars
+            .into_iter()
+            .filter_map(|(_, path)| fs::read_to_string(path).ok())
+            .map(|contents| contents.trim_start_matches('\u{feff}').to_owned())
+            .collect()
+    }
+}
+
+impl Default for PasswordHints {
+    fn default() -> Self {
+        Self::new()
+            .with_filename_pattern("[pwd=", Some("]"))
+            .with_filename_pattern("[password=", Some("]"))
+            .with_filename_pattern("[密码=", Some("]"))
+            .with_filename_pattern("解压密码：", None)
+            .with_filename_pattern("解压密码:", None)
+            .with_filename_pattern("密码：", None)
+            .with_filename_pattern("密码:", None)
+            .with_sidecar_file("password.txt")
+            .with_sidecar_file("passwords.txt")
+            .with_sidecar_file("pwd.txt")
+            .with_sidecar_file("密码.txt")
+            .with_sidecar_file("解压密码.txt")
+    }
+}
+
+impl PasswordCandidateProvider for PasswordHints {
+    fn candidates(
+        &self,
+        request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+        Ok(self
+            .hints(request.archive_name(), request.directory())
+            .into_iter()
+            .map(|password| PasswordCandidate::new(password, "hint"))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filename_patterns_yield_candidates() {
+        let hints = PasswordHints::default();
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+
+        assert_eq!(
+            hints.hints(OsStr::new("release[PWD=abc]"), sandbox.path()),
+            ["abc"]
+        );
+        assert_eq!(
+            hints.hints(OsStr::new("合集【解压密码：xyz】"), sandbox.path()),
+            ["xyz"]
+        );
+        assert_eq!(
+            hints.hints(OsStr::new("release [pwd=unterminated"), sandbox.path()),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            PasswordHints::new()
+                .with_filename_pattern("{", Some("}"))
+                .hints(OsStr::new("release{a b}"), sandbox.path()),
+            ["a b"]
+        );
+    }
+
+    #[test]
+    fn small_sidecar_files_yield_candidates() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        fs::write(
+            sandbox.path().join("Password.txt"),
+            "\u{feff}first\r\n\n解压密码：second\nfirst\n",
+        )
+        .unwrap();
+        fs::write(sandbox.path().join("密码.txt"), "third").unwrap();
+        fs::write(sandbox.path().join("pwd.txt"), vec![b'x'; 5000]).unwrap();
+        fs::write(sandbox.path().join("notes.txt"), "not a password").unwrap();
+
+        assert_eq!(
+            PasswordHints::default().hints(OsStr::new("release[pwd=name]"), sandbox.path()),
+            ["name", "first", "second", "third"]
+        );
+        assert!(
+            PasswordHints::new()
+                .hints(OsStr::new("release"), sandbox.path())
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::password_store::{ArchiveFingerprint, PasswordStore, PasswordStoreError};
+use crate::{ExtractionError, PasswordPrompt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordCandidate {
+    pub password: String,
+    pub source: String,
+    pub remember: bool,
+}
+
+impl PasswordCandidate {
+    pub fn new(password: impl Into<String>, source: impl Into<String>) -> Self {
+        Self {
+            password: password.into(),
+            source: source.into(),
+            remember: true,
+        }
+    }
+
+    pub fn without_remembering(mut self) -> Self {
+        self.remember = false;
+        self
+    }
+}
+
+pub trait PasswordCandidateProvider: Send + Sync {
+    fn candidates(
+        &self,
+        request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError>;
+}
+
+pub struct PasswordRequest<'a> {
+    archive: &'a Path,
+    archive_name: &'a OsStr,
+    directory: &'a Path,
+    store: Option<&'a PasswordStore>,
+    fingerprint: Option<&'a ArchiveFingerprint>,
+    prompt: &'a dyn PasswordPrompt,
+}
+
+impl<'a> PasswordRequest<'a> {
+    pub(crate) fn new(
+        archive: &'a Path,
+        archive_name: &'a OsStr,
+        directory: &'a Path,
+        store: Option<&'a PasswordStore>,
+        fingerprint: Option<&'a ArchiveFingerprint>,
+        prompt: &'a dyn PasswordPrompt,
+    ) -> Self {
+        Self {
+            archive,
+            archive_name,
+            directory,
+            store,
+            fingerprint,
+            prompt,
+        }
+    }
+
+    pub fn archive(&self) -> &Path {
+        self.archive
+    }
+
+    pub fn archive_name(&self) -> &OsStr {
+        self.archive_name
+    }
+
+    pub fn directory(&self) -> &Path {
+        self.directory
+    }
+
+    pub fn stored_passwords(&self) -> Result<Vec<String>, ExtractionError> {
+        let Some(store) = self.store else {
+            return Ok(Vec::new());
+        };
+        store
+            .candidates(self.fingerprint, self.prompt)
+            .map_err(|error| match error {
+                PasswordStoreError::Locked(message) => ExtractionError::PasswordStoreLocked {
+                    path: store.path().to_path_buf(),
+                    message,
+                },
+                PasswordStoreError::Failed(message) => ExtractionError::PasswordStore {
+                    path: store.path().to_path_buf(),
+                    message,
+                },
+            })
+    }
+}
+
+pub struct StoredPasswords;
+
+impl PasswordCandidateProvider for StoredPasswords {
+    fn candidates(
+        &self,
+        request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+        Ok(request
+            .stored_passwords()?
+            .into_iter()
+            .map(|password| PasswordCandidate::new(password, "stored"))
+            .collect())
+    }
+}
+
+pub struct CommandPasswords {
+    program: PathBuf,
+    arguments: Vec<OsString>,
+}
+
+impl CommandPasswords {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            arguments: Vec::new(),
+        }
+    }
+
+    pub fn with_argument(mut self, argument: impl Into<OsString>) -> Self {
+        self.arguments.push(argument.into());
+        self
+    }
+}
+
+impl PasswordCandidateProvider for CommandPasswords {
+    fn candidates(
+        &self,
+        request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+        let failed = |message: String| ExtractionError::PasswordProvider {
+            provider: "command".to_owned(),
+            message,
+        };
+        let output = Command::new(&self.program)
+            .args(&self.arguments)
+            .arg(request.archive())
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|error| {
+                failed(format!(
+                    "could not start {}: {error}",
+                    self.program.display()
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(failed(format!(
+                "{} exited with {}",
+                self.program.display(),
+                output.status
+            )));
+        }
+        Ok(lines(&String::from_utf8_lossy(&output.stdout))
+            .map(|password| PasswordCandidate::new(password, "command").without_remembering())
+            .collect())
+    }
+}
+
+pub struct EnvironmentPasswords {
+    variable: OsString,
+}
+
+impl EnvironmentPasswords {
+    pub fn new(variable: impl Into<OsString>) -> Self {
+        Self {
+            variable: variable.into(),
+        }
+    }
+}
+
+impl PasswordCandidateProvider for EnvironmentPasswords {
+    fn candidates(
+        &self,
+        _request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+        let Some(value) = std::env::var_os(&self.variable) else {
+            return Ok(Vec::new());
+        };
+        Ok(lines(&value.to_string_lossy())
+            .map(|password| PasswordCandidate::new(password, "environment").without_remembering())
+            .collect())
+    }
+}
+
+pub struct DirectoryPasswordFile {
+    name: OsString,
+    remember: bool,
+}
+
+impl DirectoryPasswordFile {
+    pub fn new(name: impl Into<OsString>) -> Self {
+        Self {
+            name: name.into(),
+            remember: false,
+        }
+    }
+
+    pub fn remembering(mut self) -> Self {
+        self.remember = true;
+        self
+    }
+}
+
+impl PasswordCandidateProvider for DirectoryPasswordFile {
+    fn candidates(
+        &self,
+        request: &PasswordRequest<'_>,
+    ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+        let path = request.directory().join(&self.name);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(ExtractionError::PasswordProvider {
+                    provider: "directory-file".to_owned(),
+                    message: format!("could not read {}: {error}", path.display()),
+                });
+            }
+        };
+        Ok(lines(contents.trim_start_matches('\u{feff}'))
+            .map(|password| {
+                let candidate = PasswordCandidate::new(password, "directory-file");
+                if self.remember {
+                    candidate
+                } else {
+                    candidate.without_remembering()
+                }
+            })
+            .collect())
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::NoPasswordPrompt;
+
+    fn passwords(candidates: Vec<PasswordCandidate>) -> Vec<(String, String, bool)> {
+        candidates
+            .into_iter()
+            .map(|candidate| (candidate.password, candidate.source, candidate.remember))
+            .collect()
+    }
+
+    #[test]
+    fn environment_and_directory_providers_yield_labelled_candidates() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.7z");
+        fs::write(
+            sandbox.path().join(".ezz-passwords"),
+            "\u{feff}one\r\n\ntwo\n",
+        )
+        .unwrap();
+        // SAFETY: the variable name is unique to this test.
+        unsafe { std::env::set_var("EZZ_TEST_PROVIDER_PASSWORDS", "from env") };
+        let request = PasswordRequest::new(
+            &archive,
+            OsStr::new("release"),
+            sandbox.path(),
+            None,
+            None,
+            &NoPasswordPrompt,
+        );
+
+        assert_eq!(
+            passwords(
+                EnvironmentPasswords::new("EZZ_TEST_PROVIDER_PASSWORDS")
+                    .candidates(&request)
+                    .unwrap()
+            ),
+            [("from env".to_owned(), "environment".to_owned(), false)]
+        );
+        assert_eq!(
+            passwords(
+                DirectoryPasswordFile::new(".ezz-passwords")
+                    .candidates(&request)
+                    .unwrap()
+            ),
+            [
+                ("one".to_owned(), "directory-file".to_owned(), false),
+                ("two".to_owned(), "directory-file".to_owned(), false),
+            ]
+        );
+        assert_eq!(
+            passwords(
+                DirectoryPasswordFile::new(".ezz-passwords")
+                    .remembering()
+                    .candidates(&request)
+                    .unwrap()
+            ),
+            [
+                ("one".to_owned(), "directory-file".to_owned(), true),
+                ("two".to_owned(), "directory-file".to_owned(), true),
+            ]
+        );
+        assert!(
+            DirectoryPasswordFile::new("missing.txt")
+                .candidates(&request)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(StoredPasswords.candidates(&request).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_provider_reads_candidates_from_standard_output() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.7z");
+        let request = PasswordRequest::new(
+            &archive,
+            OsStr::new("release"),
+            sandbox.path(),
+            None,
+            None,
+            &NoPasswordPrompt,
+        );
+
+        assert_eq!(
+            passwords(
+                CommandPasswords::new("sh")
+                    .with_argument("-c")
+                    .with_argument("case \"$0\" in *release.7z) printf 'a\\nb\\n' ;; esac")
+                    .candidates(&request)
+                    .unwrap()
+            ),
+            [
+                ("a".to_owned(), "command".to_owned(), false),
+                ("b".to_owned(), "command".to_owned(), false),
+            ]
+        );
+        assert!(matches!(
+            CommandPasswords::new("sh")
+                .with_argument("-c")
+                .with_argument("exit 3")
+                .candidates(&request),
+            Err(ExtractionError::PasswordProvider { provider, .. }) if provider == "command"
+        ));
+    }
+}
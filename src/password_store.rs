@@ -1,83 +1,682 @@
-use std::fs::{self, File};
-use std::io::{BufReader, BufWriter, Write};
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use log::warn;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::Zeroizing;
 
-const DATABASE_VERSION: u32 = 1;
+use crate::PasswordPrompt;
+use crate::workflow::NoPasswordPrompt;
+
+const PLAINTEXT_DATABASE_VERSION: u32 = 1;
+const DATABASE_VERSION: u32 = 2;
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const FINGERPRINT_HEADER_LENGTH: u64 = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordDatabaseKey {
+    Passphrase,
+    KeyFile(PathBuf),
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub(crate) enum PasswordStoreError {
+    #[error("{0}")]
+    Locked(String),
+
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for PasswordStoreError {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+pub(crate) trait PasswordBackend: Send + Sync {
+    fn location(&self) -> &Path;
+
+    fn load(&self, prompt: &dyn PasswordPrompt) -> Result<PasswordDatabase, PasswordStoreError>;
+
+    fn save(
+        &self,
+        database: &PasswordDatabase,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<(), PasswordStoreError>;
+
+    fn unlock_key(
+        &self,
+        _prompt: &dyn PasswordPrompt,
+        _writing: bool,
+    ) -> Result<(), PasswordStoreError> {
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<Option<File>, PasswordStoreError> {
+        Ok(None)
+    }
+
+    fn set_key(&mut self, _key: PasswordDatabaseKey) {
+        panic!(
+            "password database {} does not support keys",
+            self.location().display()
+        );
+    }
+
+    fn encrypted(&self) -> Result<bool, PasswordStoreError> {
+        Ok(false)
+    }
+}
 
 pub(crate) struct PasswordStore {
-    path: PathBuf,
+    backend: Box<dyn PasswordBackend>,
+    retention: PasswordRetention,
+    update: Mutex<()>,
 }
 
 impl PasswordStore {
-    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
+    pub(crate) fn new(backend: impl PasswordBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            retention: PasswordRetention::default(),
+            update: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn with_key(mut self, key: PasswordDatabaseKey) -> Self {
+        self.backend.set_key(key);
+        self
+    }
+
+    pub(crate) fn with_retention(mut self, retention: PasswordRetention) -> Self {
+        self.retention = retention;
+        self
     }
 
     pub(crate) fn path(&self) -> &Path {
-        &self.path
+        self.backend.location()
     }
 
-    pub(crate) fn candidates(&self) -> Result<Vec<String>, String> {
-        let mut database = self.load()?;
-        database.passwords.sort_by(|left, right| {
-            right
-                .last_used
-                .cmp(&left.last_used)
-                .then_with(|| right.uses.cmp(&left.uses))
-        });
-        Ok(database
-            .passwords
+    pub(crate) fn encrypted(&self) -> Result<bool, PasswordStoreError> {
+        self.backend.encrypted()
+    }
+
+    pub(crate) fn candidates(
+        &self,
+        fingerprint: Option<&ArchiveFingerprint>,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<Vec<String>, PasswordStoreError> {
+        let mut database = self.load(prompt)?;
+        let mut candidates = Vec::new();
+        if let Some(fingerprint) = fingerprint {
+            database
+                .archives
+                .sort_by_key(|archive| std::cmp::Reverse(archive.last_used));
+            let same_archive = database
+                .archives
+                .iter()
+                .filter(|archive| archive.fingerprint == fingerprint.digest);
+            let same_release = database
+                .archives
+                .iter()
+                .filter(|archive| archive.stem == fingerprint.stem);
+            for archive in same_archive.chain(same_release) {
+                if !candidates.contains(&archive.password) {
+                    candidates.push(archive.password.clone());
+                }
+            }
+        }
+        for record in ranked(database.passwords) {
+            if !candidates.contains(&record.password) {
+                candidates.push(record.password);
+            }
+        }
+        Ok(candidates)
+    }
+
+    #[cfg(test)]
+    fn record_success(
+        &self,
+        password: &str,
+        fingerprint: Option<&ArchiveFingerprint>,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<(), PasswordStoreError> {
+        self.record_attempts(&[], Some(password), fingerprint, prompt)
+    }
+
+    pub(crate) fn record_attempts(
+        &self,
+        failed: &[String],
+        succeeded: Option<&str>,
+        fingerprint: Option<&ArchiveFingerprint>,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<(), PasswordStoreError> {
+        let now = unix_now()?;
+        self.update(prompt, |database| {
+            for record in &mut database.passwords {
+                if failed.contains(&record.password) {
+                    record.failures = record.failures.saturating_add(1);
+                }
+            }
+            let Some(password) = succeeded else {
+                return;
+            };
+
+            if let Some(record) = database
+                .passwords
+                .iter_mut()
+                .find(|record| record.password == password)
+            {
+                record.uses = record.uses.saturating_add(1);
+                record.last_used = record.last_used.max(now);
+            } else {
+                database
+                    .passwords
+                    .push(PasswordRecord::new(password, 1, now));
+            }
+
+            if let Some(fingerprint) = fingerprint {
+                database
+                    .archives
+                    .retain(|archive| archive.fingerprint != fingerprint.digest);
+                database.archives.push(ArchiveRecord {
+                    fingerprint: fingerprint.digest.clone(),
+                    stem: fingerprint.stem.clone(),
+                    password: password.to_owned(),
+                    last_used: now,
+                });
+            }
+        })
+    }
+
+    fn records(
+        &self,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<Vec<PasswordRecord>, PasswordStoreError> {
+        Ok(ranked(self.load(prompt)?.passwords))
+    }
+
+    fn load(&self, prompt: &dyn PasswordPrompt) -> Result<PasswordDatabase, PasswordStoreError> {
+        let _update = self.update.lock().unwrap_or_else(PoisonError::into_inner);
+        self.backend.unlock_key(prompt, false)?;
+        let _lock = self.backend.lock()?;
+        self.backend.load(prompt)
+    }
+
+    fn update<T>(
+        &self,
+        prompt: &dyn PasswordPrompt,
+        change: impl FnOnce(&mut PasswordDatabase) -> T,
+    ) -> Result<T, PasswordStoreError> {
+        let _update = self.update.lock().unwrap_or_else(PoisonError::into_inner);
+        self.backend.unlock_key(prompt, true)?;
+        let _lock = self.backend.lock()?;
+        let mut database = self.backend.load(prompt)?;
+        let result = change(&mut database);
+        compact(&mut database, &self.retention, unix_now()?);
+        self.backend.save(&database, prompt)?;
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordRetention {
+    max_age: Option<Duration>,
+    max_records: Option<usize>,
+}
+
+impl PasswordRetention {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_max_records(mut self, max_records: usize) -> Self {
+        self.max_records = Some(max_records);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ArchiveFingerprint {
+    digest: String,
+    stem: String,
+}
+
+impl ArchiveFingerprint {
+    pub(crate) fn read(first_volume: &Path, stem: &OsStr) -> io::Result<Self> {
+        let mut header = Vec::new();
+        File::open(first_volume)?
+            .take(FINGERPRINT_HEADER_LENGTH)
+            .read_to_end(&mut header)?;
+        let stem = stem.to_string_lossy().to_lowercase();
+        let mut digest = Sha256::new();
+        digest.update(&header);
+        digest.update(stem.as_bytes());
+        Ok(Self {
+            digest: encode_hex(&digest.finalize()),
+            stem,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredPassword {
+    pub password: String,
+    pub label: Option<String>,
+    pub uses: u64,
+    pub last_used: Option<SystemTime>,
+    pub failures: u64,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PasswordDatabaseError {
+    #[error("Password database {path} is locked: {message}")]
+    Locked { path: PathBuf, message: String },
+
+    #[error("Could not use password database {path}: {message}")]
+    Failed { path: PathBuf, message: String },
+}
+
+pub struct PasswordManager {
+    store: PasswordStore,
+    prompt: Box<dyn PasswordPrompt>,
+}
+
+impl PasswordManager {
+    pub fn new(database: impl Into<PathBuf>) -> Self {
+        Self {
+            store: PasswordStore::new(JsonFileBackend::new(database)),
+            prompt: Box::new(NoPasswordPrompt),
+        }
+    }
+
+    pub fn with_key(mut self, key: PasswordDatabaseKey) -> Self {
+        self.store = self.store.with_key(key);
+        self
+    }
+
+    pub fn with_retention(mut self, retention: PasswordRetention) -> Self {
+        self.store = self.store.with_retention(retention);
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: impl PasswordPrompt + 'static) -> Self {
+        self.prompt = Box::new(prompt);
+        self
+    }
+
+    pub fn list(&self) -> Result<Vec<StoredPassword>, PasswordDatabaseError> {
+        let records = self
+            .store
+            .records(self.prompt.as_ref())
+            .map_err(|error| self.error(error))?;
+        Ok(records
             .into_iter()
-            .map(|record| record.password)
+            .map(|record| StoredPassword {
+                password: record.password,
+                label: record.label,
+                uses: record.uses,
+                last_used: (record.last_used > 0)
+                    .then(|| UNIX_EPOCH + Duration::from_secs(record.last_used)),
+                failures: record.failures,
+                pinned: record.pinned,
+            })
             .collect())
     }
 
-    pub(crate) fn record_success(&self, password: &str) -> Result<(), String> {
-        let mut database = self.load()?;
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_err(|error| error.to_string())?
-            .as_secs();
+    pub fn remove(&self, password: &str) -> Result<bool, PasswordDatabaseError> {
+        self.update(|database| {
+            let count = database.passwords.len();
+            database
+                .passwords
+                .retain(|record| record.password != password);
+            database
+                .archives
+                .retain(|archive| archive.password != password);
+            database.passwords.len() != count
+        })
+    }
 
-        if let Some(record) = database
-            .passwords
-            .iter_mut()
-            .find(|record| record.password == password)
+    pub fn set_pinned(&self, password: &str, pinned: bool) -> Result<bool, PasswordDatabaseError> {
+        self.update_record(password, |record| record.pinned = pinned)
+    }
+
+    pub fn set_label(
+        &self,
+        password: &str,
+        label: Option<&str>,
+    ) -> Result<bool, PasswordDatabaseError> {
+        let label = label
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+            .map(str::to_owned);
+        self.update_record(password, |record| record.label = label)
+    }
+
+    pub fn import(&self, list: &str) -> Result<usize, PasswordDatabaseError> {
+        self.update(|database| {
+            let passwords = &mut database.passwords;
+            let mut imported = 0;
+            for line in list.lines() {
+                let password = line.strip_suffix('\r').unwrap_or(line);
+                if password.is_empty() || passwords.iter().any(|record| record.password == password)
+                {
+                    continue;
+                }
+                passwords.push(PasswordRecord::new(password, 0, 0));
+                imported += 1;
+            }
+            imported
+        })
+    }
+
+    pub fn import_v2(&self, legacy_database: &Path) -> Result<usize, PasswordDatabaseError> {
+        let contents =
+            fs::read_to_string(legacy_database).map_err(|error| PasswordDatabaseError::Failed {
+                path: legacy_database.to_path_buf(),
+                message: error.to_string(),
+            })?;
+        self.update(|database| {
+            let mut imported = 0;
+            for (password, uses) in parse_v2_passwords(&contents) {
+                match database
+                    .passwords
+                    .iter_mut()
+                    .find(|record| record.password == password)
+                {
+                    Some(record) => record.uses = record.uses.max(uses),
+                    None => {
+                        database
+                            .passwords
+                            .push(PasswordRecord::new(&password, uses, 0));
+                        imported += 1;
+                    }
+                }
+            }
+            imported
+        })
+    }
+
+    pub fn encrypt(&self) -> Result<(), PasswordDatabaseError> {
+        self.update(|_| ())
+    }
+
+    pub fn is_encrypted(&self) -> Result<bool, PasswordDatabaseError> {
+        self.store.encrypted().map_err(|error| self.error(error))
+    }
+
+    pub fn export(&self) -> Result<String, PasswordDatabaseError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .map(|stored| stored.password + "\n")
+            .collect())
+    }
+
+    fn update_record(
+        &self,
+        password: &str,
+        change: impl FnOnce(&mut PasswordRecord),
+    ) -> Result<bool, PasswordDatabaseError> {
+        self.update(|database| {
+            database
+                .passwords
+                .iter_mut()
+                .find(|record| record.password == password)
+                .map(change)
+                .is_some()
+        })
+    }
+
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut PasswordDatabase) -> T,
+    ) -> Result<T, PasswordDatabaseError> {
+        self.store
+            .update(self.prompt.as_ref(), change)
+            .map_err(|error| self.error(error))
+    }
+
+    fn error(&self, error: PasswordStoreError) -> PasswordDatabaseError {
+        let path = self.store.path().to_path_buf();
+        match error {
+            PasswordStoreError::Locked(message) => PasswordDatabaseError::Locked { path, message },
+            PasswordStoreError::Failed(message) => PasswordDatabaseError::Failed { path, message },
+        }
+    }
+}
+
+pub(crate) struct JsonFileBackend {
+    path: PathBuf,
+    key: Option<PasswordDatabaseKey>,
+    unlocked: Mutex<Option<UnlockedKey>>,
+}
+
+impl JsonFileBackend {
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            key: None,
+            unlocked: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn with_key(mut self, key: PasswordDatabaseKey) -> Self {
+        self.set_key(key);
+        self
+    }
+
+    fn decrypt(
+        &self,
+        encrypted: &EncryptedDatabase,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<PasswordDatabase, PasswordStoreError> {
+        let nonce = decode_hex(&encrypted.nonce)
+            .filter(|nonce| nonce.len() == 24)
+            .ok_or_else(|| "password database nonce is invalid".to_owned())?;
+        let ciphertext = decode_hex(&encrypted.ciphertext)
+            .ok_or_else(|| "password database contents are invalid".to_owned())?;
+
+        let mut previous_attempt_failed = false;
+        loop {
+            let unlocked = self.unlock(
+                &encrypted.encryption,
+                prompt,
+                previous_attempt_failed,
+                false,
+            )?;
+            let cipher = XChaCha20Poly1305::new((&*unlocked.key).into());
+            match cipher.decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice()) {
+                Ok(plaintext) => {
+                    let plaintext = Zeroizing::new(plaintext);
+                    *self.unlocked.lock().unwrap_or_else(PoisonError::into_inner) = Some(unlocked);
+                    return serde_json::from_slice(&plaintext)
+                        .map_err(|error| error.to_string().into());
+                }
+                Err(_) if encrypted.encryption.key == KeyKind::Passphrase => {
+                    previous_attempt_failed = true;
+                }
+                Err(_) => {
+                    return Err(PasswordStoreError::Locked(
+                        "the configured key file does not unlock the password database".to_owned(),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn unlock(
+        &self,
+        encryption: &EncryptionHeader,
+        prompt: &dyn PasswordPrompt,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Result<UnlockedKey, PasswordStoreError> {
+        if !previous_attempt_failed
+            && let Some(unlocked) = self
+                .unlocked
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_ref()
+                .filter(|unlocked| &unlocked.encryption == encryption)
+        {
+            return Ok(unlocked.clone());
+        }
+
+        let secret = self.secret(
+            encryption.key,
+            prompt,
+            previous_attempt_failed,
+            new_passphrase,
+        )?;
+        Ok(UnlockedKey {
+            key: derive_key(&secret, encryption)?,
+            encryption: encryption.clone(),
+        })
+    }
+
+    fn secret(
+        &self,
+        kind: KeyKind,
+        prompt: &dyn PasswordPrompt,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Result<Zeroizing<Vec<u8>>, PasswordStoreError> {
+        match (kind, &self.key) {
+            (KeyKind::KeyFile, Some(PasswordDatabaseKey::KeyFile(key_file))) => {
+                fs::read(key_file).map(Zeroizing::new).map_err(|error| {
+                    PasswordStoreError::Locked(format!(
+                        "could not read password database key file {}: {error}",
+                        key_file.display()
+                    ))
+                })
+            }
+            (KeyKind::KeyFile, _) => Err(PasswordStoreError::Locked(
+                "the password database is encrypted with a key file, but no key file is configured"
+                    .to_owned(),
+            )),
+            (KeyKind::Passphrase, _) => prompt
+                .request_database_passphrase(&self.path, previous_attempt_failed, new_passphrase)
+                .map(|passphrase| Zeroizing::new(passphrase.into_bytes()))
+                .ok_or_else(|| {
+                    PasswordStoreError::Locked(
+                        "the password database passphrase was not provided".to_owned(),
+                    )
+                }),
+        }
+    }
+
+    fn encryption_key(
+        &self,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<Option<UnlockedKey>, PasswordStoreError> {
+        if let Some(unlocked) = self
+            .unlocked
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
         {
-            record.uses = record.uses.saturating_add(1);
-            record.last_used = now;
-        } else {
-            database.passwords.push(PasswordRecord {
-                password: password.to_owned(),
-                uses: 1,
-                last_used: now,
-            });
+            return Ok(Some(unlocked.clone()));
         }
+        let Some(key) = &self.key else {
+            return Ok(None);
+        };
 
-        self.save(&database)
+        let mut salt = [0_u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let encryption = EncryptionHeader {
+            key: match key {
+                PasswordDatabaseKey::Passphrase => KeyKind::Passphrase,
+                PasswordDatabaseKey::KeyFile(_) => KeyKind::KeyFile,
+            },
+            salt: encode_hex(&salt),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        };
+        let unlocked = self.unlock(&encryption, prompt, false, true)?;
+        *self.unlocked.lock().unwrap_or_else(PoisonError::into_inner) = Some(unlocked.clone());
+        Ok(Some(unlocked))
     }
+}
 
-    fn load(&self) -> Result<PasswordDatabase, String> {
+impl PasswordBackend for JsonFileBackend {
+    fn location(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self, prompt: &dyn PasswordPrompt) -> Result<PasswordDatabase, PasswordStoreError> {
         if !self.path.exists() {
             return Ok(PasswordDatabase::default());
         }
 
-        let reader = BufReader::new(File::open(&self.path).map_err(|error| error.to_string())?);
-        let database: PasswordDatabase =
-            serde_json::from_reader(reader).map_err(|error| error.to_string())?;
-        if database.version != DATABASE_VERSION {
-            return Err(format!(
-                "unsupported password database version {}",
-                database.version
-            ));
+        let contents = fs::read(&self.path).map_err(|error| error.to_string())?;
+        let header: DatabaseHeader =
+            serde_json::from_slice(&contents).map_err(|error| error.to_string())?;
+        match header.version {
+            PLAINTEXT_DATABASE_VERSION => {
+                let plaintext: PlaintextDatabase =
+                    serde_json::from_slice(&contents).map_err(|error| error.to_string())?;
+                if self.key.is_some() {
+                    self.save(&plaintext.database, prompt)?;
+                }
+                Ok(plaintext.database)
+            }
+            DATABASE_VERSION => {
+                let encrypted: EncryptedDatabase =
+                    serde_json::from_slice(&contents).map_err(|error| error.to_string())?;
+                self.decrypt(&encrypted, prompt)
+            }
+            version => Err(format!("unsupported password database version {version}").into()),
         }
-        Ok(database)
     }
 
-    fn save(&self, database: &PasswordDatabase) -> Result<(), String> {
+    fn save(
+        &self,
+        database: &PasswordDatabase,
+        prompt: &dyn PasswordPrompt,
+    ) -> Result<(), PasswordStoreError> {
+        let contents = match self.encryption_key(prompt)? {
+            Some(unlocked) => {
+                let plaintext = Zeroizing::new(
+                    serde_json::to_vec(database).map_err(|error| error.to_string())?,
+                );
+                let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let ciphertext = XChaCha20Poly1305::new((&*unlocked.key).into())
+                    .encrypt(&nonce, plaintext.as_slice())
+                    .map_err(|_| "could not encrypt the password database".to_owned())?;
+                serde_json::to_vec_pretty(&EncryptedDatabase {
+                    version: DATABASE_VERSION,
+                    encryption: unlocked.encryption,
+                    nonce: encode_hex(&nonce),
+                    ciphertext: encode_hex(&ciphertext),
+                })
+            }
+            None => serde_json::to_vec_pretty(&PlaintextDatabase {
+                version: PLAINTEXT_DATABASE_VERSION,
+                database: database.clone(),
+            }),
+        }
+        .map_err(|error| error.to_string())?;
+
         let parent = self
             .path
             .parent()
@@ -88,7 +687,8 @@ impl PasswordStore {
             tempfile::NamedTempFile::new_in(parent).map_err(|error| error.to_string())?;
         {
             let mut writer = BufWriter::new(temporary.as_file_mut());
-            serde_json::to_writer_pretty(&mut writer, database)
+            writer
+                .write_all(&contents)
                 .map_err(|error| error.to_string())?;
             writer.write_all(b"\n").map_err(|error| error.to_string())?;
             writer.flush().map_err(|error| error.to_string())?;
@@ -103,28 +703,317 @@ impl PasswordStore {
             .map_err(|error| error.error.to_string())?;
         Ok(())
     }
+
+    fn unlock_key(
+        &self,
+        prompt: &dyn PasswordPrompt,
+        writing: bool,
+    ) -> Result<(), PasswordStoreError> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                if writing {
+                    self.encryption_key(prompt)?;
+                }
+                return Ok(());
+            }
+            Err(error) => return Err(error.to_string().into()),
+        };
+        match serde_json::from_slice::<DatabaseHeader>(&contents).map(|header| header.version) {
+            Ok(DATABASE_VERSION) => {
+                if let Ok(encrypted) = serde_json::from_slice::<EncryptedDatabase>(&contents) {
+                    self.decrypt(&encrypted, prompt)?;
+                }
+            }
+            Ok(PLAINTEXT_DATABASE_VERSION) => {
+                self.encryption_key(prompt)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn set_key(&mut self, key: PasswordDatabaseKey) {
+        self.key = Some(key);
+        *self
+            .unlocked
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn encrypted(&self) -> Result<bool, PasswordStoreError> {
+        if self.key.is_some() {
+            return Ok(true);
+        }
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(serde_json::from_slice::<DatabaseHeader>(&contents)
+                .is_ok_and(|header| header.version == DATABASE_VERSION)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.to_string().into()),
+        }
+    }
+
+    fn lock(&self) -> Result<Option<File>, PasswordStoreError> {
+        let parent = self
+            .path
+            .parent()
+            .ok_or_else(|| "password database has no parent directory".to_owned())?;
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+
+        let mut lock_path = self.path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|error| error.to_string())?;
+        match lock_file.lock() {
+            Ok(()) => Ok(Some(lock_file)),
+            Err(error) if error.kind() == io::ErrorKind::Unsupported => {
+                warn!(
+                    "file locking is not supported for {}: {error}",
+                    self.path.display()
+                );
+                Ok(None)
+            }
+            Err(error) => Err(format!("could not lock the password database: {error}").into()),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct PasswordDatabase {
+#[derive(Clone)]
+struct UnlockedKey {
+    encryption: EncryptionHeader,
+    key: Zeroizing<[u8; KEY_LENGTH]>,
+}
+
+#[derive(Deserialize)]
+struct DatabaseHeader {
     version: u32,
-    passwords: Vec<PasswordRecord>,
 }
 
-impl Default for PasswordDatabase {
-    fn default() -> Self {
-        Self {
-            version: DATABASE_VERSION,
-            passwords: Vec::new(),
-        }
-    }
+#[derive(Serialize, Deserialize)]
+struct PlaintextDatabase {
+    version: u32,
+    #[serde(flatten)]
+    database: PasswordDatabase,
 }
 
 #[derive(Serialize, Deserialize)]
+struct EncryptedDatabase {
+    version: u32,
+    encryption: EncryptionHeader,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct EncryptionHeader {
+    key: KeyKind,
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum KeyKind {
+    Passphrase,
+    KeyFile,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct PasswordDatabase {
+    passwords: Vec<PasswordRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    archives: Vec<ArchiveRecord>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 struct PasswordRecord {
     password: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
     uses: u64,
     last_used: u64,
+    #[serde(default)]
+    failures: u64,
+    #[serde(default)]
+    added: u64,
+    #[serde(default)]
+    pinned: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ArchiveRecord {
+    fingerprint: String,
+    stem: String,
+    password: String,
+    last_used: u64,
+}
+
+impl PasswordRecord {
+    fn new(password: &str, uses: u64, last_used: u64) -> Self {
+        Self {
+            password: password.to_owned(),
+            label: None,
+            uses,
+            last_used,
+            failures: 0,
+            added: 0,
+            pinned: false,
+        }
+    }
+
+    fn never_hit(&self) -> bool {
+        self.uses == 0 && self.failures > 0
+    }
+}
+
+fn parse_v2_passwords(contents: &str) -> Vec<(String, u64)> {
+    let mut passwords: Vec<(String, u64)> = Vec::new();
+    for line in contents.trim_start_matches('\u{feff}').lines().skip(1) {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let (password, uses) = match line.split_once(' ') {
+            Some((uses, password)) if uses.bytes().all(|byte| byte.is_ascii_digit()) => {
+                (password, uses.parse().unwrap_or(u64::MAX))
+            }
+            _ => (line, 0),
+        };
+        if password.is_empty() {
+            continue;
+        }
+        match passwords
+            .iter_mut()
+            .find(|(existing, _)| existing == password)
+        {
+            Some((_, existing_uses)) => *existing_uses = (*existing_uses).max(uses),
+            None => passwords.push((password.to_owned(), uses)),
+        }
+    }
+    passwords
+}
+
+fn ranked(mut passwords: Vec<PasswordRecord>) -> Vec<PasswordRecord> {
+    passwords.sort_by(|left, right| {
+        right
+            .pinned
+            .cmp(&left.pinned)
+            .then_with(|| left.never_hit().cmp(&right.never_hit()))
+            .then_with(|| right.last_used.cmp(&left.last_used))
+            .then_with(|| right.uses.cmp(&left.uses))
+            .then_with(|| left.failures.cmp(&right.failures))
+    });
+    passwords
+}
+
+fn compact(database: &mut PasswordDatabase, retention: &PasswordRetention, now: u64) {
+    let mut passwords: Vec<PasswordRecord> = Vec::new();
+    for mut record in database.passwords.drain(..) {
+        if record.added == 0 {
+            record.added = now;
+        }
+        match passwords
+            .iter_mut()
+            .find(|existing| existing.password == record.password)
+        {
+            Some(existing) => {
+                existing.uses = existing.uses.max(record.uses);
+                existing.last_used = existing.last_used.max(record.last_used);
+                existing.failures = existing.failures.max(record.failures);
+                existing.added = existing.added.min(record.added);
+                existing.pinned |= record.pinned;
+                if existing.label.is_none() {
+                    existing.label = record.label;
+                }
+            }
+            None => passwords.push(record),
+        }
+    }
+
+    if let Some(max_age) = retention.max_age {
+        let cutoff = now.saturating_sub(max_age.as_secs());
+        passwords.retain(|record| record.pinned || record.last_used.max(record.added) >= cutoff);
+    }
+    if let Some(max_records) = retention.max_records
+        && passwords.len() > max_records
+    {
+        let pinned = passwords.iter().filter(|record| record.pinned).count();
+        let allowed = max_records.saturating_sub(pinned);
+        let mut kept = 0;
+        passwords = ranked(passwords)
+            .into_iter()
+            .filter(|record| {
+                if record.pinned {
+                    return true;
+                }
+                kept += 1;
+                kept <= allowed
+            })
+            .collect();
+    }
+
+    database.archives.retain(|archive| {
+        passwords
+            .iter()
+            .any(|record| record.password == archive.password)
+    });
+    if let Some(max_age) = retention.max_age {
+        let cutoff = now.saturating_sub(max_age.as_secs());
+        database
+            .archives
+            .retain(|archive| archive.last_used >= cutoff);
+    }
+    if let Some(max_records) = retention.max_records {
+        database
+            .archives
+            .sort_by_key(|archive| std::cmp::Reverse(archive.last_used));
+        database.archives.truncate(max_records);
+    }
+    database.passwords = passwords;
+}
+
+fn unix_now() -> Result<u64, PasswordStoreError> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|error| error.to_string())?
+        .as_secs())
+}
+
+fn derive_key(
+    secret: &[u8],
+    encryption: &EncryptionHeader,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>, PasswordStoreError> {
+    let salt = decode_hex(&encryption.salt)
+        .ok_or_else(|| "password database salt is invalid".to_owned())?;
+    let params = Params::new(
+        encryption.memory_kib,
+        encryption.iterations,
+        encryption.parallelism,
+        Some(KEY_LENGTH),
+    )
+    .map_err(|error| error.to_string())?;
+    let mut key = Zeroizing::new([0_u8; KEY_LENGTH]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(secret, &salt, &mut *key)
+        .map_err(|error| error.to_string())?;
+    Ok(key)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
 }
 
 #[cfg(unix)]
@@ -164,3 +1053,443 @@ fn set_private_permissions(path: &Path) -> std::io::Result<()> {
         )))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::PasswordResponse;
+
+    struct PassphrasePrompt {
+        passphrases: Mutex<VecDeque<String>>,
+        requests: Mutex<Vec<(bool, bool)>>,
+    }
+
+    impl PassphrasePrompt {
+        fn new<'a>(passphrases: impl IntoIterator<Item = &'a str>) -> Self {
+            Self {
+                passphrases: Mutex::new(passphrases.into_iter().map(str::to_owned).collect()),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl PasswordPrompt for PassphrasePrompt {
+        fn request_password(
+            &self,
+            _input: &Path,
+            _previous_attempt_failed: bool,
+            _comment: Option<&str>,
+        ) -> Option<PasswordResponse> {
+            None
+        }
+
+        fn request_database_passphrase(
+            &self,
+            database: &Path,
+            _previous_attempt_failed: bool,
+            new_passphrase: bool,
+        ) -> Option<String> {
+            let mut lock_path = database.as_os_str().to_owned();
+            lock_path.push(".lock");
+            let lock_free = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(lock_path)
+                .is_ok_and(|lock| lock.try_lock().is_ok());
+            self.requests
+                .lock()
+                .unwrap()
+                .push((new_passphrase, lock_free));
+            self.passphrases.lock().unwrap().pop_front()
+        }
+    }
+
+    struct MemoryBackend {
+        database: Mutex<PasswordDatabase>,
+    }
+
+    impl PasswordBackend for MemoryBackend {
+        fn location(&self) -> &Path {
+            Path::new("memory")
+        }
+
+        fn load(
+            &self,
+            _prompt: &dyn PasswordPrompt,
+        ) -> Result<PasswordDatabase, PasswordStoreError> {
+            Ok(self.database.lock().unwrap().clone())
+        }
+
+        fn save(
+            &self,
+            database: &PasswordDatabase,
+            _prompt: &dyn PasswordPrompt,
+        ) -> Result<(), PasswordStoreError> {
+            *self.database.lock().unwrap() = database.clone();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn store_ranks_and_records_through_its_backend() {
+        let prompt = PassphrasePrompt::new([]);
+        let store = PasswordStore::new(MemoryBackend {
+            database: Mutex::new(PasswordDatabase {
+                passwords: vec![
+                    PasswordRecord::new("older", 9, 1),
+                    PasswordRecord::new("newer", 1, 2),
+                ],
+                archives: Vec::new(),
+            }),
+        });
+
+        assert_eq!(store.candidates(None, &prompt).unwrap(), ["newer", "older"]);
+        store.record_success("older", None, &prompt).unwrap();
+        assert_eq!(store.candidates(None, &prompt).unwrap(), ["older", "newer"]);
+        assert_eq!(store.path(), Path::new("memory"));
+    }
+
+    #[test]
+    #[should_panic(expected = "password database memory does not support keys")]
+    fn keys_are_never_silently_dropped_with_their_backend() {
+        let _ = PasswordStore::new(MemoryBackend {
+            database: Mutex::new(PasswordDatabase::default()),
+        })
+        .with_key(PasswordDatabaseKey::Passphrase);
+    }
+
+    #[test]
+    fn failures_and_the_working_password_are_recorded_together() {
+        let prompt = PassphrasePrompt::new([]);
+        let store = PasswordStore::new(MemoryBackend {
+            database: Mutex::new(PasswordDatabase {
+                passwords: vec![PasswordRecord::new("wrong", 0, 1)],
+                archives: Vec::new(),
+            }),
+        });
+
+        store
+            .record_attempts(&["wrong".to_owned()], Some("right"), None, &prompt)
+            .unwrap();
+
+        let records = store.records(&prompt).unwrap();
+        assert_eq!(records[0].password, "right");
+        assert_eq!(records[0].uses, 1);
+        assert_eq!(records[1].password, "wrong");
+        assert_eq!(records[1].failures, 1);
+    }
+
+    #[test]
+    fn keys_are_applied_to_the_configured_backend() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let database = sandbox.path().join("passwords.json");
+        let key_file = sandbox.path().join("passwords.key");
+        fs::write(&key_file, b"key file contents").unwrap();
+        let prompt = PassphrasePrompt::new([]);
+        let store = PasswordStore::new(JsonFileBackend::new(&database))
+            .with_retention(PasswordRetention::new().with_max_records(1))
+            .with_key(PasswordDatabaseKey::KeyFile(key_file));
+
+        store.record_success("older", None, &prompt).unwrap();
+        store.record_success("newer", None, &prompt).unwrap();
+
+        assert!(store.encrypted().unwrap());
+        assert!(
+            fs::read_to_string(&database)
+                .unwrap()
+                .contains("ciphertext")
+        );
+        assert_eq!(store.candidates(None, &prompt).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn archive_passwords_are_tried_first_for_the_same_release() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let first = sandbox.path().join("release.part1.rar");
+        let resplit = sandbox.path().join("Release.7z.001");
+        let unrelated = sandbox.path().join("other.zip");
+        fs::write(&first, b"Rar!\x1a\x07\x01\x00 first split").unwrap();
+        fs::write(&resplit, b"7z\xbc\xaf\x27\x1c second split").unwrap();
+        fs::write(&unrelated, b"PK\x03\x04").unwrap();
+        let fingerprint =
+            |path: &Path, stem: &str| ArchiveFingerprint::read(path, OsStr::new(stem)).unwrap();
+        let prompt = PassphrasePrompt::new([]);
+        let store = PasswordStore::new(MemoryBackend {
+            database: Mutex::new(PasswordDatabase::default()),
+        });
+
+        store
+            .record_success(
+                "release secret",
+                Some(&fingerprint(&first, "release")),
+                &prompt,
+            )
+            .unwrap();
+        store.record_success("recent", None, &prompt).unwrap();
+        store.record_success("recent", None, &prompt).unwrap();
+
+        assert_eq!(
+            store
+                .candidates(Some(&fingerprint(&first, "release")), &prompt)
+                .unwrap(),
+            ["release secret", "recent"]
+        );
+        assert_eq!(
+            store
+                .candidates(Some(&fingerprint(&resplit, "Release")), &prompt)
+                .unwrap(),
+            ["release secret", "recent"]
+        );
+        assert_eq!(
+            store
+                .candidates(Some(&fingerprint(&unrelated, "other")), &prompt)
+                .unwrap(),
+            ["recent", "release secret"]
+        );
+        assert_ne!(
+            fingerprint(&first, "release"),
+            fingerprint(&resplit, "release")
+        );
+    }
+
+    #[test]
+    fn compaction_prunes_stale_records_and_caps_the_list() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 100 * DAY;
+        let record = |password: &str, uses, last_used, failures, pinned| PasswordRecord {
+            failures,
+            added: last_used,
+            pinned,
+            ..PasswordRecord::new(password, uses, last_used)
+        };
+        let mut database = PasswordDatabase {
+            passwords: vec![
+                record("stale", 9, now - 40 * DAY, 0, false),
+                record("stale but pinned", 0, now - 40 * DAY, 0, true),
+                record("never hit", 0, now - DAY, 5, false),
+                record("recent", 1, now - DAY, 0, false),
+                record("older", 4, now - 2 * DAY, 0, false),
+                record("recent", 3, now - 3 * DAY, 0, false),
+            ],
+            archives: vec![ArchiveRecord {
+                fingerprint: "digest".to_owned(),
+                stem: "release".to_owned(),
+                password: "stale".to_owned(),
+                last_used: now - 40 * DAY,
+            }],
+        };
+
+        compact(
+            &mut database,
+            &PasswordRetention::new()
+                .with_max_age(Duration::from_secs(30 * DAY))
+                .with_max_records(2),
+            now,
+        );
+
+        let passwords: Vec<_> = database
+            .passwords
+            .iter()
+            .map(|record| (record.password.as_str(), record.uses))
+            .collect();
+        assert_eq!(passwords, [("stale but pinned", 0), ("recent", 3)]);
+        assert!(database.archives.is_empty());
+
+        let ranked: Vec<_> = ranked(vec![
+            record("never hit", 0, now, 5, false),
+            record("imported", 0, 0, 0, false),
+            record("used", 1, now - DAY, 2, false),
+        ])
+        .into_iter()
+        .map(|record| record.password)
+        .collect();
+        assert_eq!(ranked, ["used", "imported", "never hit"]);
+    }
+
+    #[test]
+    fn compaction_applies_the_same_limits_to_archive_records() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 100 * DAY;
+        let archive = |fingerprint: &str, last_used| ArchiveRecord {
+            fingerprint: fingerprint.to_owned(),
+            stem: "release".to_owned(),
+            password: "pinned".to_owned(),
+            last_used,
+        };
+        let mut database = PasswordDatabase {
+            passwords: vec![PasswordRecord {
+                pinned: true,
+                ..PasswordRecord::new("pinned", 1, now)
+            }],
+            archives: vec![
+                archive("stale", now - 40 * DAY),
+                archive("older", now - 3 * DAY),
+                archive("newest", now - DAY),
+                archive("newer", now - 2 * DAY),
+            ],
+        };
+
+        compact(
+            &mut database,
+            &PasswordRetention::new()
+                .with_max_age(Duration::from_secs(30 * DAY))
+                .with_max_records(2),
+            now,
+        );
+
+        let fingerprints: Vec<_> = database
+            .archives
+            .iter()
+            .map(|archive| archive.fingerprint.as_str())
+            .collect();
+        assert_eq!(fingerprints, ["newest", "newer"]);
+    }
+
+    #[test]
+    fn v2_password_lists_keep_their_use_counts() {
+        assert_eq!(
+            parse_v2_passwords("\u{feff}0 0 0\r\n12 first\n3 with spaces \n\nbare\n2 first\n"),
+            [
+                ("first".to_owned(), 12),
+                ("with spaces ".to_owned(), 3),
+                ("bare".to_owned(), 0),
+            ]
+        );
+        assert!(parse_v2_passwords("0 0 0").is_empty());
+    }
+
+    const HAMMER_DATABASE: &str = "EZZ_PASSWORD_STORE_HAMMER_DATABASE";
+    const HAMMER_UPDATES: u64 = 20;
+
+    #[test]
+    fn concurrent_stores_never_lose_updates() {
+        if let Some(database) = std::env::var_os(HAMMER_DATABASE) {
+            let store = PasswordStore::new(JsonFileBackend::new(database));
+            for _ in 0..HAMMER_UPDATES {
+                store
+                    .record_success("shared", None, &PassphrasePrompt::new([]))
+                    .unwrap();
+            }
+            return;
+        }
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let database = sandbox.path().join("passwords.json");
+        let processes: Vec<_> = (0..2)
+            .map(|_| {
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args([
+                        "--exact",
+                        "password_store::tests::concurrent_stores_never_lose_updates",
+                        "--test-threads=1",
+                    ])
+                    .env(HAMMER_DATABASE, &database)
+                    .stdout(std::process::Stdio::null())
+                    .spawn()
+                    .expect("start hammer process")
+            })
+            .collect();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let store = PasswordStore::new(JsonFileBackend::new(&database));
+                    for _ in 0..HAMMER_UPDATES {
+                        store
+                            .record_success("shared", None, &PassphrasePrompt::new([]))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+        for mut process in processes {
+            assert!(process.wait().unwrap().success());
+        }
+
+        let records = PasswordStore::new(JsonFileBackend::new(&database))
+            .records(&PassphrasePrompt::new([]))
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].uses, 6 * HAMMER_UPDATES);
+    }
+
+    #[test]
+    fn plaintext_database_is_migrated_to_the_key_file_format() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let database = sandbox.path().join("passwords.json");
+        let key_file = sandbox.path().join("ezz.key");
+        fs::write(&key_file, b"a key file with enough entropy").unwrap();
+        let no_prompt = PassphrasePrompt::new([]);
+        PasswordStore::new(JsonFileBackend::new(&database))
+            .record_success("legacy secret", None, &no_prompt)
+            .unwrap();
+
+        let store = PasswordStore::new(
+            JsonFileBackend::new(&database).with_key(PasswordDatabaseKey::KeyFile(key_file)),
+        );
+        assert_eq!(
+            store.candidates(None, &no_prompt).unwrap(),
+            ["legacy secret"]
+        );
+
+        let contents = fs::read_to_string(&database).unwrap();
+        assert!(contents.contains("\"version\": 2"));
+        assert!(!contents.contains("legacy secret"));
+        assert!(matches!(
+            PasswordStore::new(JsonFileBackend::new(&database)).candidates(None, &no_prompt),
+            Err(PasswordStoreError::Locked(_))
+        ));
+    }
+
+    #[test]
+    fn passphrase_is_requested_again_after_a_wrong_attempt() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let database = sandbox.path().join("passwords.json");
+        PasswordStore::new(
+            JsonFileBackend::new(&database).with_key(PasswordDatabaseKey::Passphrase),
+        )
+        .record_success("archive secret", None, &PassphrasePrompt::new(["unlock"]))
+        .unwrap();
+
+        let store = PasswordStore::new(
+            JsonFileBackend::new(&database).with_key(PasswordDatabaseKey::Passphrase),
+        );
+        assert_eq!(
+            store
+                .candidates(None, &PassphrasePrompt::new(["wrong", "unlock"]))
+                .unwrap(),
+            ["archive secret"]
+        );
+        assert_eq!(
+            PasswordStore::new(JsonFileBackend::new(&database))
+                .candidates(None, &PassphrasePrompt::new([])),
+            Err(PasswordStoreError::Locked(
+                "the password database passphrase was not provided".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn passphrase_is_confirmed_on_creation_and_requested_before_locking() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let database = sandbox.path().join("passwords.json");
+        let creating = PassphrasePrompt::new(["unlock"]);
+        PasswordStore::new(
+            JsonFileBackend::new(&database).with_key(PasswordDatabaseKey::Passphrase),
+        )
+        .record_success("archive secret", None, &creating)
+        .unwrap();
+        assert_eq!(*creating.requests.lock().unwrap(), [(true, true)]);
+
+        let opening = PassphrasePrompt::new(["wrong", "unlock"]);
+        PasswordStore::new(JsonFileBackend::new(&database))
+            .record_success("archive secret", None, &opening)
+            .unwrap();
+        assert_eq!(
+            *opening.requests.lock().unwrap(),
+            [(false, true), (false, true)]
+        );
+    }
+}
@@ -1,14 +1,22 @@
 use std::error::Error;
 use std::fs::{self, OpenOptions};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
-use ezz::{BatchReport, ExtractionWarning};
+use ezz::{
+    BatchReport, DesktopApplication, ExtractionWarning, FolderWatcher, PasswordDatabaseKey,
+    PasswordManager, PasswordPrompt,
+};
 use log::{error, info, warn};
 use notify_rust::Notification;
 use simplelog::{Config, LevelFilter, WriteLogger};
 
+pub const CONCURRENT_EXTRACTIONS: usize = 2;
+
 pub struct PlatformPaths {
     pub password_database: PathBuf,
+    pub password_key_file: PathBuf,
     pub log_file: PathBuf,
 }
 
@@ -18,12 +26,10 @@ impl PlatformPaths {
         {
             let home = std::env::var_os("HOME").ok_or("HOME is not set")?;
             let home = PathBuf::from(home);
+            let data = home.join("Library").join("Application Support").join("ezz");
             Ok(Self {
-                password_database: home
-                    .join("Library")
-                    .join("Application Support")
-                    .join("ezz")
-                    .join("passwords.json"),
+                password_database: data.join("passwords.json"),
+                password_key_file: data.join("passwords.key"),
                 log_file: home
                     .join("Library")
                     .join("Logs")
@@ -39,10 +45,17 @@ impl PlatformPaths {
                 PathBuf::from(std::env::var_os("LOCALAPPDATA").ok_or("LOCALAPPDATA is not set")?);
             Ok(Self {
                 password_database: roaming.join("ezz").join("passwords.json"),
+                password_key_file: roaming.join("ezz").join("passwords.key"),
                 log_file: local.join("ezz").join("logs").join("ezz.log"),
             })
         }
     }
+
+    pub fn password_database_key(&self) -> Option<PasswordDatabaseKey> {
+        self.password_key_file
+            .is_file()
+            .then(|| PasswordDatabaseKey::KeyFile(self.password_key_file.clone()))
+    }
 }
 
 pub fn initialize_logging(path: &std::path::Path) -> Result<(), Box<dyn Error>> {
@@ -58,18 +71,211 @@ pub fn notify_started(count: usize) {
     let body = if count == 1 {
         "Extracting 1 file".to_owned()
     } else {
-        format!("Extracting {count} files in order")
+        format!("Extracting {count} files")
     };
     show_notification("ezz", &body);
 }
 
+pub fn watch_directories() -> Option<Vec<PathBuf>> {
+    let mut arguments = std::env::args_os().skip(1);
+    if arguments.next()? != "--watch" {
+        return None;
+    }
+    Some(arguments.map(PathBuf::from).collect())
+}
+
+pub fn watch(
+    application: &DesktopApplication,
+    directories: Vec<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    if directories.is_empty() {
+        return Err("--watch requires at least one directory".into());
+    }
+    let mut watcher = FolderWatcher::new(&directories, false)?;
+    for directory in &directories {
+        info!("watching {}", directory.display());
+    }
+    show_notification("ezz", "Watching for completed downloads");
+    application.watch(&mut watcher, finish_batch)?;
+    Ok(())
+}
+
+pub fn verify_inputs() -> Option<Vec<PathBuf>> {
+    let mut arguments = std::env::args_os().skip(1);
+    if arguments.next()? != "--verify" {
+        return None;
+    }
+    Some(arguments.map(PathBuf::from).collect())
+}
+
+pub fn verify(
+    application: &DesktopApplication,
+    inputs: Vec<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    if inputs.is_empty() {
+        return Err("--verify requires at least one archive".into());
+    }
+    let verified = application.verify_files(inputs);
+    let mut failed = 0;
+    for file in &verified {
+        match &file.result {
+            Ok(report) if report.is_ok() => {
+                info!("verified {}", file.input.display());
+                println!("OK\t{}", file.input.display());
+            }
+            Ok(report) => {
+                failed += 1;
+                for problem in &report.problems {
+                    warn!("verification of {} found {problem}", file.input.display());
+                    println!("FAILED\t{}\t{problem}", file.input.display());
+                }
+            }
+            Err(verification_error) => {
+                failed += 1;
+                error!(
+                    "failed to verify {}: {verification_error}",
+                    file.input.display()
+                );
+                println!("ERROR\t{}\t{verification_error}", file.input.display());
+            }
+        }
+    }
+    show_notification(
+        "Verification complete",
+        &format!("{} passed, {failed} failed", verified.len() - failed),
+    );
+    Ok(())
+}
+
+pub fn manage_passwords(
+    paths: &PlatformPaths,
+    prompt: impl PasswordPrompt + 'static,
+) -> Option<Result<(), Box<dyn Error>>> {
+    let mut arguments = std::env::args().skip(1);
+    if arguments.next()? != "--passwords" {
+        return None;
+    }
+    let arguments: Vec<_> = arguments.collect();
+    let mut manager = PasswordManager::new(&paths.password_database).with_prompt(prompt);
+    match paths.password_database_key() {
+        Some(key) => manager = manager.with_key(key),
+        None if arguments
+            .first()
+            .is_some_and(|command| command == "encrypt") =>
+        {
+            manager = manager.with_key(PasswordDatabaseKey::Passphrase);
+        }
+        None => {}
+    }
+    Some(run_password_command(&manager, &arguments))
+}
+
+fn run_password_command(
+    manager: &PasswordManager,
+    arguments: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<_> = arguments.iter().map(String::as_str).collect();
+    let summary = match arguments.as_slice() {
+        ["list"] => {
+            let passwords = manager.list()?;
+            for stored in &passwords {
+                let last_used = stored
+                    .last_used
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|elapsed| elapsed.as_secs().to_string())
+                    .unwrap_or_else(|| "never".to_owned());
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    stored.password,
+                    stored.label.as_deref().unwrap_or(""),
+                    stored.uses,
+                    stored.failures,
+                    last_used,
+                    if stored.pinned { "pinned" } else { "" }
+                );
+            }
+            format!("{} saved passwords", passwords.len())
+        }
+        ["remove", password] => changed(manager.remove(password)?, "Removed the password"),
+        ["pin", password] => changed(manager.set_pinned(password, true)?, "Pinned the password"),
+        ["unpin", password] => changed(
+            manager.set_pinned(password, false)?,
+            "Unpinned the password",
+        ),
+        ["label", password, label] => changed(
+            manager.set_label(password, Some(label))?,
+            "Updated the label",
+        ),
+        ["unlabel", password] => changed(manager.set_label(password, None)?, "Removed the label"),
+        ["import", file] => {
+            let imported = manager.import(&fs::read_to_string(file)?)?;
+            format!("Imported {imported} new passwords")
+        }
+        ["import-v2", file] => {
+            let imported = manager.import_v2(Path::new(file))?;
+            format!("Imported {imported} passwords from the v2 password file")
+        }
+        ["encrypt"] => {
+            manager.encrypt()?;
+            "Encrypted the password database".to_owned()
+        }
+        ["export", file] => {
+            if manager.is_encrypted()? {
+                return Err(
+                    "the password database is encrypted and will not be exported as plaintext"
+                        .into(),
+                );
+            }
+            write_private_file(Path::new(file), &manager.export()?)?;
+            format!("Exported saved passwords to {file}")
+        }
+        _ => {
+            return Err(
+                "usage: --passwords list | remove <password> | pin <password> | \
+                        unpin <password> | label <password> <label> | unlabel <password> | \
+                        import <file> | import-v2 <file> | export <file> | encrypt"
+                    .into(),
+            );
+        }
+    };
+    info!("password database command {}: {summary}", arguments[0]);
+    show_notification("ezz", &summary);
+    Ok(())
+}
+
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+fn changed(found: bool, summary: &str) -> String {
+    if found {
+        summary.to_owned()
+    } else {
+        "The password is not saved".to_owned()
+    }
+}
+
 pub fn finish_batch(report: &BatchReport) {
     let succeeded = report
         .files
         .iter()
         .filter(|outcome| outcome.result.is_ok())
-        .count();
-    let failed = report.files.len() - succeeded;
+        .count()
+        + report
+            .created_archives
+            .iter()
+            .filter(|created| created.result.is_ok())
+            .count();
+    let failed = report.files.len() + report.created_archives.len() - succeeded
+        + report
+            .expanded_directories
+            .iter()
+            .filter(|expansion| expansion.result.is_err())
+            .count();
     let warnings = report
         .files
         .iter()
@@ -85,6 +291,19 @@ pub fn finish_batch(report: &BatchReport) {
                     outcome.input.display(),
                     outcome.output.display()
                 );
+                for output in &outcome.additional_outputs {
+                    info!(
+                        "extracted {} to {}",
+                        outcome.input.display(),
+                        output.display()
+                    );
+                }
+                for source in &outcome.password_sources {
+                    info!(
+                        "opened {} with a password from {source}",
+                        outcome.input.display()
+                    );
+                }
                 for warning in &outcome.warnings {
                     log_warning(warning);
                 }
@@ -98,6 +317,35 @@ pub fn finish_batch(report: &BatchReport) {
         }
     }
 
+    for expansion in &report.expanded_directories {
+        match &expansion.result {
+            Ok(expanded) => info!(
+                "found {} archives in {} and skipped {} other files",
+                expanded.archives.len(),
+                expansion.directory.display(),
+                expanded.skipped.len()
+            ),
+            Err(expansion_error) => error!(
+                "failed to scan {}: {expansion_error}",
+                expansion.directory.display()
+            ),
+        }
+    }
+
+    for created in &report.created_archives {
+        match &created.result {
+            Ok(outcome) => info!(
+                "archived {} to {}",
+                created.input.display(),
+                outcome.output.display()
+            ),
+            Err(creation_error) => error!(
+                "failed to archive {}: {creation_error}",
+                created.input.display()
+            ),
+        }
+    }
+
     let mut body = format!("{succeeded} succeeded, {failed} failed");
     if warnings > 0 {
         body.push_str(&format!(", {warnings} warnings"));
@@ -119,6 +367,14 @@ fn log_warning(warning: &ExtractionWarning) {
             "could not update password database {}: {message}",
             path.display()
         ),
+        ExtractionWarning::RecursiveExtractionStopped { archive, reason } => warn!(
+            "kept nested archive {} without extracting it: {reason}",
+            archive.display()
+        ),
+        ExtractionWarning::MetadataNotRestored { path, message } => warn!(
+            "could not restore archived metadata of {}: {message}",
+            path.display()
+        ),
     }
 }
 
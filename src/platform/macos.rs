@@ -17,7 +17,10 @@ use objc2_foundation::{
     NSObjectProtocol, NSPoint, NSRect, NSSize, NSString, ns_string,
 };
 
-use super::common::{PlatformPaths, finish_batch, initialize_logging, notify_started};
+use super::common::{
+    CONCURRENT_EXTRACTIONS, PlatformPaths, finish_batch, initialize_logging, manage_passwords,
+    notify_started, verify, verify_inputs, watch, watch_directories,
+};
 
 struct AppDelegateIvars {
     application: DesktopApplication,
@@ -115,6 +118,7 @@ impl PasswordPrompt for MacPasswordPrompt {
         &self,
         input: &Path,
         previous_attempt_failed: bool,
+        comment: Option<&str>,
     ) -> Option<PasswordResponse> {
         let mtm = MainThreadMarker::new().expect("password prompt must run on the main thread");
         let alert = NSAlert::new(mtm);
@@ -123,11 +127,15 @@ impl PasswordPrompt for MacPasswordPrompt {
             .file_name()
             .map(|name| name.to_string_lossy())
             .unwrap_or_else(|| input.as_os_str().to_string_lossy());
-        let information = if previous_attempt_failed {
+        let mut information = if previous_attempt_failed {
             format!("The password for {filename} was incorrect. Try again.")
         } else {
             format!("Enter the password for {filename}.")
         };
+        if let Some(comment) = comment {
+            information.push_str("\n\nArchive comment:\n");
+            information.push_str(comment);
+        }
         alert.setInformativeText(&NSString::from_str(&information));
         alert.addButtonWithTitle(ns_string!("Extract"));
         alert.addButtonWithTitle(ns_string!("Cancel"));
@@ -181,19 +189,101 @@ impl PasswordPrompt for MacPasswordPrompt {
             keep_original: keep_original.state() == NSControlStateValueOn,
         })
     }
+
+    fn request_database_passphrase(
+        &self,
+        database: &Path,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Option<String> {
+        let mtm = MainThreadMarker::new().expect("passphrase prompt must run on the main thread");
+        let mut mismatched = false;
+        loop {
+            let alert = NSAlert::new(mtm);
+            let information = if mismatched {
+                "The passphrases did not match. Enter the new passphrase twice.".to_owned()
+            } else if new_passphrase {
+                format!(
+                    "Choose a passphrase to encrypt the password database {}.",
+                    database.display()
+                )
+            } else if previous_attempt_failed {
+                "The passphrase was incorrect. Try again.".to_owned()
+            } else {
+                format!(
+                    "Enter the passphrase for the password database {}.",
+                    database.display()
+                )
+            };
+            if new_passphrase {
+                alert.setMessageText(ns_string!("Set a passphrase"));
+                alert.addButtonWithTitle(ns_string!("Encrypt"));
+            } else {
+                alert.setMessageText(ns_string!("Password database locked"));
+                alert.addButtonWithTitle(ns_string!("Unlock"));
+            }
+            alert.setInformativeText(&NSString::from_str(&information));
+            alert.addButtonWithTitle(ns_string!("Cancel"));
+
+            let height = if new_passphrase { 58.0 } else { 24.0 };
+            let accessory = NSView::initWithFrame(
+                NSView::alloc(mtm),
+                NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, height)),
+            );
+            let passphrase = NSSecureTextField::initWithFrame(
+                NSSecureTextField::alloc(mtm),
+                NSRect::new(NSPoint::new(0.0, height - 24.0), NSSize::new(360.0, 24.0)),
+            );
+            passphrase.setPlaceholderString(Some(ns_string!("Passphrase")));
+            accessory.addSubview(&passphrase);
+            let confirmation = NSSecureTextField::initWithFrame(
+                NSSecureTextField::alloc(mtm),
+                NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 24.0)),
+            );
+            confirmation.setPlaceholderString(Some(ns_string!("Confirm passphrase")));
+            if new_passphrase {
+                accessory.addSubview(&confirmation);
+            }
+            alert.setAccessoryView(Some(&accessory));
+
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let passphrase = passphrase.stringValue().to_string();
+            if !new_passphrase || passphrase == confirmation.stringValue().to_string() {
+                return Some(passphrase);
+            }
+            mismatched = true;
+        }
+    }
 }
 
 pub fn run() -> Result<(), Box<dyn Error>> {
     let paths = PlatformPaths::discover()?;
     initialize_logging(&paths.log_file)?;
+    if let Some(result) = manage_passwords(&paths, MacPasswordPrompt) {
+        return result;
+    }
+
     let executable = std::env::current_exe()?;
     let seven_zip = executable.with_file_name("7zz");
-    let workflow = ExtractionWorkflow::with_password_support(
+    let password_database_key = paths.password_database_key();
+    let mut workflow = ExtractionWorkflow::with_password_support(
         seven_zip,
         paths.password_database,
         MacPasswordPrompt,
     );
-    let desktop_application = DesktopApplication::new(workflow);
+    if let Some(key) = password_database_key {
+        workflow = workflow.with_password_database_key(key);
+    }
+    let desktop_application =
+        DesktopApplication::new(workflow).with_concurrency(CONCURRENT_EXTRACTIONS);
+    if let Some(directories) = watch_directories() {
+        return watch(&desktop_application, directories);
+    }
+    if let Some(inputs) = verify_inputs() {
+        return verify(&desktop_application, inputs);
+    }
 
     let mtm = MainThreadMarker::new().ok_or("ezz must start on the main thread")?;
     let app = NSApplication::sharedApplication(mtm);
@@ -15,7 +15,10 @@ use native_windows_gui as nwg;
 use nwg::NativeUi;
 use serde::{Deserialize, Serialize};
 
-use super::common::{PlatformPaths, finish_batch, initialize_logging, notify_started};
+use super::common::{
+    CONCURRENT_EXTRACTIONS, PlatformPaths, finish_batch, initialize_logging, manage_passwords,
+    notify_started, verify, verify_inputs, watch, watch_directories,
+};
 
 const INSTANCE_NAME: &str = "io.github.yangmoooo.ezz.v3";
 const IDLE_TIMEOUT: Duration = Duration::from_millis(750);
@@ -35,7 +38,7 @@ pub struct PasswordDialog {
     #[nwg_control(
         title: "Password required",
         center: true,
-        size: (390, 225),
+        size: (390, 313),
         flags: "WINDOW|VISIBLE",
         icon: Some(&data.icon)
     )]
@@ -51,6 +54,14 @@ pub struct PasswordDialog {
 
     #[nwg_control(
         position: (20, 64),
+        size: (350, 80),
+        readonly: true,
+        flags: "VISIBLE|VSCROLL|AUTOVSCROLL"
+    )]
+    comment: nwg::TextBox,
+
+    #[nwg_control(
+        position: (20, 152),
         size: (350, 25),
         limit: 1024,
         password: Some('*'),
@@ -60,7 +71,7 @@ pub struct PasswordDialog {
 
     #[nwg_control(
         text: "Remember this password",
-        position: (20, 101),
+        position: (20, 189),
         size: (350, 24),
         check_state: nwg::CheckBoxState::Checked
     )]
@@ -68,16 +79,16 @@ pub struct PasswordDialog {
 
     #[nwg_control(
         text: "Keep the original archive",
-        position: (20, 130),
+        position: (20, 218),
         size: (350, 24)
     )]
     keep_original: nwg::CheckBox,
 
-    #[nwg_control(text: "Extract", position: (194, 172), size: (82, 30))]
+    #[nwg_control(text: "Extract", position: (194, 260), size: (82, 30))]
     #[nwg_events(OnButtonClick: [PasswordDialog::accept])]
     accept_button: nwg::Button,
 
-    #[nwg_control(text: "Cancel", position: (288, 172), size: (82, 30))]
+    #[nwg_control(text: "Cancel", position: (288, 260), size: (82, 30))]
     #[nwg_events(OnButtonClick: [PasswordDialog::cancel])]
     cancel_button: nwg::Button,
 
@@ -88,6 +99,7 @@ impl PasswordDialog {
     fn show(
         input: &Path,
         previous_attempt_failed: bool,
+        comment: Option<&str>,
     ) -> Result<Option<PasswordResponse>, nwg::NwgError> {
         let dialog = PasswordDialog::build_ui(Default::default())?;
         let filename = input
@@ -100,10 +112,33 @@ impl PasswordDialog {
             format!("Enter the password for {filename}.")
         };
         dialog.information.set_text(&information);
+        match comment {
+            Some(comment) => dialog
+                .comment
+                .set_text(&comment.lines().collect::<Vec<_>>().join("\r\n")),
+            None => dialog.collapse_comment(),
+        }
         nwg::dispatch_thread_events();
         Ok(dialog.response.borrow_mut().take())
     }
 
+    fn collapse_comment(&self) {
+        const COMMENT_HEIGHT: i32 = 88;
+        self.comment.set_visible(false);
+        let (x, y) = self.password.position();
+        self.password.set_position(x, y - COMMENT_HEIGHT);
+        let (x, y) = self.remember.position();
+        self.remember.set_position(x, y - COMMENT_HEIGHT);
+        let (x, y) = self.keep_original.position();
+        self.keep_original.set_position(x, y - COMMENT_HEIGHT);
+        let (x, y) = self.accept_button.position();
+        self.accept_button.set_position(x, y - COMMENT_HEIGHT);
+        let (x, y) = self.cancel_button.position();
+        self.cancel_button.set_position(x, y - COMMENT_HEIGHT);
+        let (width, height) = self.window.size();
+        self.window.set_size(width, height - COMMENT_HEIGHT as u32);
+    }
+
     fn focus_password(&self) {
         self.password.set_focus();
     }
@@ -122,6 +157,126 @@ impl PasswordDialog {
     }
 }
 
+#[derive(Default, NwgUi)]
+pub struct PassphraseDialog {
+    #[nwg_resource(source_bin: Some(ICON_DATA))]
+    icon: nwg::Icon,
+
+    #[nwg_control(
+        title: "Password database locked",
+        center: true,
+        size: (390, 181),
+        flags: "WINDOW|VISIBLE",
+        icon: Some(&data.icon)
+    )]
+    #[nwg_events(
+        OnInit: [PassphraseDialog::focus_passphrase],
+        OnKeyEnter: [PassphraseDialog::accept],
+        OnWindowClose: [PassphraseDialog::cancel]
+    )]
+    window: nwg::Window,
+
+    #[nwg_control(position: (20, 16), size: (350, 42))]
+    information: nwg::Label,
+
+    #[nwg_control(
+        position: (20, 64),
+        size: (350, 25),
+        limit: 1024,
+        password: Some('*'),
+        placeholder_text: Some("Passphrase"),
+        focus: true
+    )]
+    passphrase: nwg::TextInput,
+
+    #[nwg_control(
+        position: (20, 97),
+        size: (350, 25),
+        limit: 1024,
+        password: Some('*'),
+        placeholder_text: Some("Confirm passphrase")
+    )]
+    confirmation: nwg::TextInput,
+
+    #[nwg_control(text: "Unlock", position: (194, 138), size: (82, 30))]
+    #[nwg_events(OnButtonClick: [PassphraseDialog::accept])]
+    accept_button: nwg::Button,
+
+    #[nwg_control(text: "Cancel", position: (288, 138), size: (82, 30))]
+    #[nwg_events(OnButtonClick: [PassphraseDialog::cancel])]
+    cancel_button: nwg::Button,
+
+    new_passphrase: std::cell::Cell<bool>,
+    response: std::cell::RefCell<Option<String>>,
+}
+
+impl PassphraseDialog {
+    fn show(
+        database: &Path,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Result<Option<String>, nwg::NwgError> {
+        let dialog = PassphraseDialog::build_ui(Default::default())?;
+        dialog.new_passphrase.set(new_passphrase);
+        let information = if new_passphrase {
+            format!(
+                "Choose a passphrase to encrypt the password database {}.",
+                database.display()
+            )
+        } else if previous_attempt_failed {
+            "The passphrase was incorrect. Try again.".to_owned()
+        } else {
+            format!(
+                "Enter the passphrase for the password database {}.",
+                database.display()
+            )
+        };
+        dialog.information.set_text(&information);
+        if new_passphrase {
+            dialog.window.set_text("Set a passphrase");
+            dialog.accept_button.set_text("Encrypt");
+        } else {
+            dialog.collapse_confirmation();
+        }
+        nwg::dispatch_thread_events();
+        Ok(dialog.response.borrow_mut().take())
+    }
+
+    fn collapse_confirmation(&self) {
+        const CONFIRMATION_HEIGHT: i32 = 33;
+        self.confirmation.set_visible(false);
+        let (x, y) = self.accept_button.position();
+        self.accept_button.set_position(x, y - CONFIRMATION_HEIGHT);
+        let (x, y) = self.cancel_button.position();
+        self.cancel_button.set_position(x, y - CONFIRMATION_HEIGHT);
+        let (width, height) = self.window.size();
+        self.window
+            .set_size(width, height - CONFIRMATION_HEIGHT as u32);
+    }
+
+    fn focus_passphrase(&self) {
+        self.passphrase.set_focus();
+    }
+
+    fn accept(&self) {
+        let passphrase = self.passphrase.text();
+        if self.new_passphrase.get() && passphrase != self.confirmation.text() {
+            self.information
+                .set_text("The passphrases did not match. Enter the new passphrase twice.");
+            self.passphrase.set_text("");
+            self.confirmation.set_text("");
+            self.passphrase.set_focus();
+            return;
+        }
+        self.response.replace(Some(passphrase));
+        nwg::stop_thread_dispatch();
+    }
+
+    fn cancel(&self) {
+        nwg::stop_thread_dispatch();
+    }
+}
+
 struct WindowsPasswordPrompt;
 
 impl PasswordPrompt for WindowsPasswordPrompt {
@@ -129,8 +284,9 @@ impl PasswordPrompt for WindowsPasswordPrompt {
         &self,
         input: &Path,
         previous_attempt_failed: bool,
+        comment: Option<&str>,
     ) -> Option<PasswordResponse> {
-        match PasswordDialog::show(input, previous_attempt_failed) {
+        match PasswordDialog::show(input, previous_attempt_failed, comment) {
             Ok(response) => response,
             Err(error) => {
                 warn!("could not show password dialog: {error}");
@@ -138,6 +294,21 @@ impl PasswordPrompt for WindowsPasswordPrompt {
             }
         }
     }
+
+    fn request_database_passphrase(
+        &self,
+        database: &Path,
+        previous_attempt_failed: bool,
+        new_passphrase: bool,
+    ) -> Option<String> {
+        match PassphraseDialog::show(database, previous_attempt_failed, new_passphrase) {
+            Ok(response) => response,
+            Err(error) => {
+                warn!("could not show passphrase dialog: {error}");
+                None
+            }
+        }
+    }
 }
 
 pub fn run() -> Result<(), Box<dyn Error>> {
@@ -145,6 +316,34 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     initialize_logging(&paths.log_file)?;
     nwg::init()?;
     nwg::Font::set_global_family("Segoe UI")?;
+    if std::env::args_os()
+        .nth(1)
+        .is_some_and(|argument| argument == "--passwords" || argument == "--verify")
+    {
+        attach_parent_console();
+    }
+
+    if let Some(result) = manage_passwords(&paths, WindowsPasswordPrompt) {
+        return result;
+    }
+
+    let executable = std::env::current_exe()?;
+    let password_database_key = paths.password_database_key();
+    let mut workflow = ExtractionWorkflow::with_password_support(
+        executable.with_file_name("7zz.exe"),
+        paths.password_database,
+        WindowsPasswordPrompt,
+    );
+    if let Some(key) = password_database_key {
+        workflow = workflow.with_password_database_key(key);
+    }
+    let application = DesktopApplication::new(workflow).with_concurrency(CONCURRENT_EXTRACTIONS);
+    if let Some(directories) = watch_directories() {
+        return watch(&application, directories);
+    }
+    if let Some(inputs) = verify_inputs() {
+        return verify(&application, inputs);
+    }
 
     let initial_paths: Vec<_> = std::env::args_os().skip(1).map(PathBuf::from).collect();
     let instance_name = INSTANCE_NAME.to_ns_name::<GenericNamespaced>()?;
@@ -161,13 +360,6 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     };
 
     let receiver = start_instance_listener(listener);
-    let executable = std::env::current_exe()?;
-    let workflow = ExtractionWorkflow::with_password_support(
-        executable.with_file_name("7zz.exe"),
-        paths.password_database,
-        WindowsPasswordPrompt,
-    );
-    let application = DesktopApplication::new(workflow);
     let mut pending: VecDeque<_> = initial_paths.into();
     if pending.is_empty() {
         pending.extend(select_files()?);
@@ -195,6 +387,21 @@ pub fn run() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    if unsafe { AttachConsole(ATTACH_PARENT_PROCESS) } == 0 {
+        info!(
+            "no parent console to attach: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
 pub fn show_fatal_error(message: &str) {
     let _ = nwg::init();
     nwg::error_message("ezz could not start", message);
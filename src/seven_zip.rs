@@ -1,13 +1,102 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::workflow::ExtractionError;
+use crate::workflow::{ExtractionError, VerificationProblem};
 
 pub(crate) struct SevenZip {
     executable: PathBuf,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EmbeddedArchive {
+    pub(crate) path: PathBuf,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CreateError {
+    Launch {
+        path: PathBuf,
+        message: String,
+    },
+    Failed {
+        exit_code: Option<i32>,
+        message: String,
+    },
+}
+
+pub(crate) struct CreateSwitches<'a> {
+    pub(crate) archive_type: &'static str,
+    pub(crate) password: Option<&'a str>,
+    pub(crate) encrypt_headers: bool,
+    pub(crate) volume_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveListing {
+    pub properties: ArchiveProperties,
+    pub entries: Vec<ArchiveEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveProperties {
+    pub archive_type: Option<String>,
+    pub solid: bool,
+    pub multivolume: bool,
+    pub volume_index: Option<u32>,
+    pub volumes: Option<u32>,
+    pub header_encrypted: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub size: Option<u64>,
+    pub packed_size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub crc: Option<u32>,
+    pub attributes: Option<String>,
+    pub mode: Option<u32>,
+    pub encrypted: bool,
+    pub method: Option<String>,
+    pub is_dir: bool,
+    pub symlink_target: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct TestReport {
+    pub(crate) problems: Vec<VerificationProblem>,
+    pub(crate) password_rejected: bool,
+}
+
+impl ArchiveListing {
+    pub(crate) fn smallest_encrypted_entry(&self) -> Option<String> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.encrypted && !entry.is_dir)
+            .filter_map(|entry| Some((entry.size?, entry)))
+            .min_by_key(|(size, _)| *size)
+            .map(|(_, entry)| entry.path.to_string_lossy().into_owned())
+    }
+
+    pub(crate) fn validate_paths(&self) -> Result<(), ExtractionError> {
+        for entry in &self.entries {
+            if is_unsafe_archive_path(&entry.path.to_string_lossy()) {
+                return Err(ExtractionError::UnsafeOutput {
+                    path: entry.path.clone(),
+                    reason: "archive entry escapes the extraction directory".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
 impl SevenZip {
     pub(crate) fn new(executable: impl Into<PathBuf>) -> Self {
         Self {
@@ -16,10 +105,21 @@ impl SevenZip {
     }
 
     pub(crate) fn probe(&self, input: &Path) -> Result<(), ExtractionError> {
+        self.list(input, "").map(|_| ())
+    }
+
+    pub(crate) fn list(
+        &self,
+        input: &Path,
+        password: &str,
+    ) -> Result<ArchiveListing, ExtractionError> {
         let mut command = Command::new(&self.executable);
         command
+            .env("TZ", "UTC")
             .arg("l")
-            .args(["-slt", "-ba", "-p", "-bsp0", "-sccUTF-8", "-scsUTF-8"])
+            .arg("-slt")
+            .arg(password_switch(password))
+            .args(["-bsp0", "-sccUTF-8", "-scsUTF-8"])
             .arg(input);
         let output = command
             .output()
@@ -29,7 +129,9 @@ impl SevenZip {
             })?;
 
         if output.status.success() {
-            return Ok(());
+            return Ok(parse_archive_listing(&String::from_utf8_lossy(
+                &output.stdout,
+            )));
         }
 
         let message = output_message(&output);
@@ -46,10 +148,10 @@ impl SevenZip {
         }
     }
 
-    pub(crate) fn embedded_archive(
+    pub(crate) fn embedded_archives(
         &self,
         input: &Path,
-    ) -> Result<Option<PathBuf>, ExtractionError> {
+    ) -> Result<Vec<EmbeddedArchive>, ExtractionError> {
         let mut command = Command::new(&self.executable);
         command
             .arg("l")
@@ -73,7 +175,7 @@ impl SevenZip {
         if !output.status.success() {
             let message = output_message(&output);
             if message.contains("Cannot open the file as archive") {
-                return Ok(None);
+                return Ok(Vec::new());
             }
             return Err(ExtractionError::EngineFailed {
                 operation: "scan embedded data in",
@@ -82,7 +184,7 @@ impl SevenZip {
             });
         }
 
-        Ok(find_embedded_archive(&String::from_utf8_lossy(
+        Ok(find_embedded_archives(&String::from_utf8_lossy(
             &output.stdout,
         )))
     }
@@ -138,35 +240,38 @@ impl SevenZip {
                 path: self.executable.clone(),
                 message: error.to_string(),
             })?;
-
-        if output.status.success() {
-            Ok(())
-        } else {
-            let message = output_message(&output);
-            if is_wrong_password(&message) {
-                Err(ExtractionError::WrongPassword)
-            } else {
-                Err(ExtractionError::EngineFailed {
-                    operation: "test",
-                    exit_code: output.status.code(),
-                    message,
-                })
-            }
-        }
+        password_test_result(&output)
     }
 
-    pub(crate) fn validate_paths(
+    pub(crate) fn verify(
         &self,
         input: &Path,
         password: &str,
-    ) -> Result<(), ExtractionError> {
+    ) -> Result<TestReport, ExtractionError> {
         let mut command = Command::new(&self.executable);
         command
-            .arg("l")
-            .args(["-slt", "-ba"])
+            .arg("t")
             .arg(password_switch(password))
             .args(["-bsp0", "-sccUTF-8", "-scsUTF-8"])
             .arg(input);
+        self.test_report(command)
+    }
+
+    pub(crate) fn verify_embedded(
+        &self,
+        input: &Path,
+        embedded: &[EmbeddedArchive],
+    ) -> Result<TestReport, ExtractionError> {
+        let mut command = Command::new(&self.executable);
+        command
+            .arg("t")
+            .args(["-t#", "-p", "-bsp0", "-sccUTF-8", "-scsUTF-8"])
+            .arg(input)
+            .args(embedded.iter().map(|archive| &archive.path));
+        self.test_report(command)
+    }
+
+    fn test_report(&self, mut command: Command) -> Result<TestReport, ExtractionError> {
         let output = command
             .output()
             .map_err(|error| ExtractionError::EngineLaunch {
@@ -174,20 +279,46 @@ impl SevenZip {
                 message: error.to_string(),
             })?;
 
-        if output.status.success() {
-            validate_listed_paths(&String::from_utf8_lossy(&output.stdout))
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push('\n');
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        let mut report = parse_test_report(&text);
+        if !output.status.success() && report.problems.is_empty() {
+            report.problems.push(VerificationProblem::Other {
+                message: output_message(&output),
+            });
+        }
+        Ok(report)
+    }
+
+    pub(crate) fn spawn_password_test(
+        &self,
+        input: &Path,
+        password: &str,
+        entry: Option<&str>,
+    ) -> Result<PasswordTest, ExtractionError> {
+        let mut command = Command::new(&self.executable);
+        command.arg("t").arg(password_switch(password)).args([
+            "-bso0",
+            "-bsp0",
+            "-sccUTF-8",
+            "-scsUTF-8",
+        ]);
+        if let Some(entry) = entry {
+            command.arg("-spd").arg("--").arg(input).arg(entry);
         } else {
-            let message = output_message(&output);
-            if is_wrong_password(&message) {
-                Err(ExtractionError::WrongPassword)
-            } else {
-                Err(ExtractionError::EngineFailed {
-                    operation: "validate archive paths in",
-                    exit_code: output.status.code(),
-                    message,
-                })
-            }
+            command.arg(input);
         }
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| ExtractionError::EngineLaunch {
+                path: self.executable.clone(),
+                message: error.to_string(),
+            })?;
+        Ok(PasswordTest { child })
     }
 
     pub(crate) fn extract(
@@ -195,6 +326,7 @@ impl SevenZip {
         input: &Path,
         output_dir: &Path,
         password: &str,
+        include: &[String],
     ) -> Result<(), ExtractionError> {
         let mut output_switch = OsString::from("-o");
         output_switch.push(output_dir);
@@ -212,6 +344,7 @@ impl SevenZip {
                 "-sccUTF-8",
                 "-scsUTF-8",
             ])
+            .args(include.iter().map(|pattern| format!("-i!{pattern}")))
             .arg(input);
         let output = command
             .output()
@@ -238,25 +371,130 @@ impl SevenZip {
             }
         }
     }
+
+    pub(crate) fn create(
+        &self,
+        working_directory: &Path,
+        names: &[&OsStr],
+        archive: &Path,
+        switches: &CreateSwitches<'_>,
+    ) -> Result<(), CreateError> {
+        let mut command = Command::new(&self.executable);
+        command
+            .current_dir(working_directory)
+            .arg("a")
+            .arg(format!("-t{}", switches.archive_type))
+            .args(["-bso0", "-bsp0", "-sccUTF-8", "-scsUTF-8"]);
+        if let Some(password) = switches.password {
+            command.arg(password_switch(password));
+        }
+        if switches.encrypt_headers {
+            command.arg("-mhe=on");
+        }
+        if let Some(volume_size) = switches.volume_size {
+            command.arg(format!("-v{volume_size}b"));
+        }
+        command.arg("--").arg(archive).args(names);
+        let output = command.output().map_err(|error| CreateError::Launch {
+            path: self.executable.clone(),
+            message: error.to_string(),
+        })?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(CreateError::Failed {
+                exit_code: output.status.code(),
+                message: output_message(&output),
+            })
+        }
+    }
+}
+
+pub(crate) struct PasswordTest {
+    child: Child,
 }
 
-fn find_embedded_archive(output: &str) -> Option<PathBuf> {
+impl PasswordTest {
+    pub(crate) fn poll(&mut self) -> Option<Result<(), ExtractionError>> {
+        match self.child.try_wait() {
+            Ok(None) => None,
+            Ok(Some(_)) => Some(self.finish()),
+            Err(error) => Some(Err(ExtractionError::EngineFailed {
+                operation: "test",
+                exit_code: None,
+                message: error.to_string(),
+            })),
+        }
+    }
+
+    pub(crate) fn cancel(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    fn finish(&mut self) -> Result<(), ExtractionError> {
+        let mut stderr = Vec::new();
+        if let Some(mut pipe) = self.child.stderr.take() {
+            let _ = pipe.read_to_end(&mut stderr);
+        }
+        let status = self
+            .child
+            .wait()
+            .map_err(|error| ExtractionError::EngineFailed {
+                operation: "test",
+                exit_code: None,
+                message: error.to_string(),
+            })?;
+        password_test_result(&Output {
+            status,
+            stdout: Vec::new(),
+            stderr,
+        })
+    }
+}
+
+fn password_test_result(output: &Output) -> Result<(), ExtractionError> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = output_message(output);
+        if is_wrong_password(&message) {
+            Err(ExtractionError::WrongPassword)
+        } else {
+            Err(ExtractionError::EngineFailed {
+                operation: "test",
+                exit_code: output.status.code(),
+                message,
+            })
+        }
+    }
+}
+
+fn find_embedded_archives(output: &str) -> Vec<EmbeddedArchive> {
+    let mut archives = Vec::new();
     let mut path: Option<PathBuf> = None;
     let mut archive_type = None;
     let mut offset = None;
+    let mut size = None;
 
     for line in output.lines().chain(std::iter::once("")) {
         if line.is_empty() {
-            if offset.is_some_and(|offset| offset > 0)
+            if let Some(offset) = offset.filter(|offset| *offset > 0)
                 && archive_type.is_some_and(is_supported_embedded_type)
                 && let Some(path) = path.take()
                 && is_safe_relative_path(&path)
             {
-                return Some(path);
+                archives.push(EmbeddedArchive {
+                    path,
+                    offset,
+                    size: size.unwrap_or(0),
+                });
             }
             path = None;
             archive_type = None;
             offset = None;
+            size = None;
             continue;
         }
 
@@ -266,10 +504,223 @@ fn find_embedded_archive(output: &str) -> Option<PathBuf> {
             archive_type = Some(value);
         } else if let Some(value) = line.strip_prefix("Offset = ") {
             offset = value.parse::<u64>().ok();
+        } else if let Some(value) = line.strip_prefix("Size = ") {
+            size = value.parse::<u64>().ok();
         }
     }
 
-    None
+    archives.sort_by_key(|archive| archive.offset);
+    archives
+}
+
+fn parse_test_report(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    for line in output.lines().map(str::trim) {
+        report.password_rejected |= is_wrong_password(line);
+        let line = line.strip_prefix("ERROR: ").unwrap_or(line);
+        let (message, name) = line.rsplit_once(" : ").unwrap_or((line, ""));
+        let problem = if message.starts_with("CRC Failed") {
+            VerificationProblem::CrcError {
+                entry: PathBuf::from(name),
+            }
+        } else if message.starts_with("Data Error") {
+            VerificationProblem::DataError {
+                entry: PathBuf::from(name),
+            }
+        } else if message.starts_with("Missing volume") {
+            VerificationProblem::MissingVolume {
+                volume: PathBuf::from(name),
+            }
+        } else if message.contains("data after the end") {
+            VerificationProblem::DataAfterEnd
+        } else if message.starts_with("Unexpected end of") {
+            VerificationProblem::UnexpectedEnd
+        } else {
+            continue;
+        };
+        if !report.problems.contains(&problem) {
+            report.problems.push(problem);
+        }
+    }
+    report
+}
+
+fn parse_archive_listing(output: &str) -> ArchiveListing {
+    let mut listing = ArchiveListing::default();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("----------") {
+            break;
+        }
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let value = multiline_value(value, &mut lines);
+        let properties = &mut listing.properties;
+        match name {
+            "Type" if properties.archive_type.is_none() => properties.archive_type = Some(value),
+            "Solid" => properties.solid = value == "+",
+            "Multivolume" => properties.multivolume = value == "+",
+            "Volume Index" => properties.volume_index = value.parse().ok(),
+            "Volumes" => properties.volumes = value.parse().ok(),
+            "Encrypted" => properties.header_encrypted = value == "+",
+            "Comment" => properties.comment = Some(value),
+            _ => {}
+        }
+    }
+
+    let mut entry = None::<ArchiveEntry>;
+    let mut lines = lines.chain(std::iter::once(""));
+    while let Some(line) = lines.next() {
+        if line.is_empty() {
+            listing.entries.extend(entry.take());
+            continue;
+        }
+        let Some((name, value)) = line.split_once(" = ") else {
+            continue;
+        };
+        let value = multiline_value(value, &mut lines);
+        let entry = entry.get_or_insert_with(ArchiveEntry::default);
+        match name {
+            "Path" => entry.path = PathBuf::from(value),
+            "Folder" => entry.is_dir = value == "+",
+            "Size" => entry.size = value.parse().ok(),
+            "Packed Size" => entry.packed_size = value.parse().ok(),
+            "Modified" => entry.modified = parse_listed_time(&value),
+            "CRC" => entry.crc = u32::from_str_radix(&value, 16).ok(),
+            "Attributes" => {
+                entry.is_dir |= value.starts_with('D');
+                if entry.mode.is_none() {
+                    entry.mode = value.split_whitespace().find_map(parse_mode);
+                }
+                entry.attributes = Some(value);
+            }
+            "Mode" => {
+                entry.is_dir |= value.starts_with('d');
+                entry.mode = parse_mode(&value);
+            }
+            "Encrypted" => entry.encrypted = value == "+",
+            "Method" => entry.method = Some(value),
+            "Symbolic Link" if !value.is_empty() => {
+                entry.symlink_target = Some(PathBuf::from(value));
+            }
+            _ => {}
+        }
+    }
+    listing
+}
+
+fn parse_mode(value: &str) -> Option<u32> {
+    let value = value.as_bytes();
+    if value.len() != 10 || !b"-dlbcps".contains(&value[0]) {
+        return None;
+    }
+    let mut mode = 0;
+    for (index, &flag) in value[1..].iter().enumerate() {
+        let shift = 8 - index as u32;
+        let special = match index {
+            2 => 0o4000,
+            5 => 0o2000,
+            8 => 0o1000,
+            _ => 0,
+        };
+        mode |= match (index % 3, flag) {
+            (_, b'-') => 0,
+            (0, b'r') | (1, b'w') | (2, b'x') => 1 << shift,
+            (2, b's') if index != 8 => special | 1 << shift,
+            (2, b'S') if index != 8 => special,
+            (2, b't') if index == 8 => special | 1,
+            (2, b'T') if index == 8 => special,
+            _ => return None,
+        };
+    }
+    Some(mode)
+}
+
+fn multiline_value<'a>(value: &str, lines: &mut impl Iterator<Item = &'a str>) -> String {
+    if value != "{" {
+        return value.to_owned();
+    }
+    let mut collected = Vec::new();
+    for line in lines.by_ref() {
+        if line == "}" {
+            break;
+        }
+        collected.push(line);
+    }
+    collected.join("\n")
+}
+
+fn parse_listed_time(value: &str) -> Option<SystemTime> {
+    let (date, time) = value.split_once(' ')?;
+    let mut date = date.split('-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.split(':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        format!("{fraction:0<9}").get(..9)?.parse::<u64>().ok()?
+    };
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    let whole = Duration::from_secs(seconds.unsigned_abs());
+    let time = if seconds >= 0 {
+        UNIX_EPOCH.checked_add(whole)?
+    } else {
+        UNIX_EPOCH.checked_sub(whole)?
+    };
+    listed_time_to_utc(time.checked_add(Duration::from_nanos(nanos))?)
+}
+
+#[cfg(not(windows))]
+fn listed_time_to_utc(time: SystemTime) -> Option<SystemTime> {
+    Some(time)
+}
+
+#[cfg(windows)]
+#[repr(C)]
+struct FileTime {
+    low: u32,
+    high: u32,
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn LocalFileTimeToFileTime(local: *const FileTime, utc: *mut FileTime) -> i32;
+}
+
+#[cfg(windows)]
+const FILETIME_EPOCH_OFFSET: Duration = Duration::from_secs(11_644_473_600);
+
+#[cfg(windows)]
+fn listed_time_to_utc(local: SystemTime) -> Option<SystemTime> {
+    let ticks = (local.checked_add(FILETIME_EPOCH_OFFSET)?)
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos()
+        / 100;
+    let ticks = u64::try_from(ticks).ok()?;
+    let local = FileTime {
+        low: ticks as u32,
+        high: (ticks >> 32) as u32,
+    };
+    let mut utc = FileTime { low: 0, high: 0 };
+    if unsafe { LocalFileTimeToFileTime(&local, &mut utc) } == 0 {
+        return None;
+    }
+    let ticks = u64::from(utc.high) << 32 | u64::from(utc.low);
+    UNIX_EPOCH
+        .checked_add(Duration::from_nanos(ticks.checked_mul(100)?))?
+        .checked_sub(FILETIME_EPOCH_OFFSET)
 }
 
 fn is_supported_embedded_type(archive_type: &str) -> bool {
@@ -286,21 +737,6 @@ fn is_safe_relative_path(path: &Path) -> bool {
             .all(|component| matches!(component, std::path::Component::Normal(_)))
 }
 
-fn validate_listed_paths(output: &str) -> Result<(), ExtractionError> {
-    for path in output
-        .lines()
-        .filter_map(|line| line.strip_prefix("Path = "))
-    {
-        if is_unsafe_archive_path(path) {
-            return Err(ExtractionError::UnsafeOutput {
-                path: PathBuf::from(path),
-                reason: "archive entry escapes the extraction directory".to_owned(),
-            });
-        }
-    }
-    Ok(())
-}
-
 fn is_unsafe_archive_path(path: &str) -> bool {
     let bytes = path.as_bytes();
     path.is_empty()
@@ -327,3 +763,190 @@ fn output_message(output: &Output) -> String {
         String::from_utf8_lossy(&output.stdout).trim().to_owned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_yields_structured_problems() {
+        let report = parse_test_report(
+            "Testing archive: release.7z.001\n\
+             ERROR: CRC Failed : docs/readme.txt\n\
+             ERROR: Data Error in encrypted file. Wrong password? : large.bin\n\
+             ERROR: Missing volume : release.7z.003\n\
+             \n\
+             WARNINGS:\n\
+             There are data after the end of archive\n\
+             ERRORS:\n\
+             Unexpected end of archive\n\
+             \n\
+             Sub items Errors: 2\n",
+        );
+
+        assert!(report.password_rejected);
+        assert_eq!(
+            report.problems,
+            [
+                VerificationProblem::CrcError {
+                    entry: PathBuf::from("docs/readme.txt")
+                },
+                VerificationProblem::DataError {
+                    entry: PathBuf::from("large.bin")
+                },
+                VerificationProblem::MissingVolume {
+                    volume: PathBuf::from("release.7z.003")
+                },
+                VerificationProblem::DataAfterEnd,
+                VerificationProblem::UnexpectedEnd,
+            ]
+        );
+        assert_eq!(
+            parse_test_report("Everything is Ok\n"),
+            TestReport::default()
+        );
+    }
+
+    #[test]
+    fn technical_listing_yields_typed_entries_and_properties() {
+        let listing = parse_archive_listing(
+            "Listing archive: release.rar\n\
+             \n\
+             --\n\
+             Path = release.rar\n\
+             Type = Rar5\n\
+             Solid = +\n\
+             Multivolume = +\n\
+             Volume Index = 0\n\
+             Volumes = 2\n\
+             Comment = {\n\
+             password: abc\n\
+             enjoy\n\
+             }\n\
+             \n\
+             ----------\n\
+             Path = docs\n\
+             Folder = +\n\
+             Attributes = D\n\
+             \n\
+             Path = docs/readme.txt\n\
+             Size = 12\n\
+             Packed Size = 16\n\
+             Modified = 2024-02-29 12:34:56.5\n\
+             CRC = 0000ABCD\n\
+             Encrypted = +\n\
+             Method = AES-256:Deflate\n\
+             \n\
+             Path = docs/link\n\
+             Symbolic Link = readme.txt\n\
+             \n\
+             Path = docs/run.sh\n\
+             Attributes = A_ -rwxr-sr-x\n\
+             \n\
+             Path = tmp\n\
+             Mode = drwxrwxrwt\n\
+             Symbolic Link = \n",
+        );
+
+        assert_eq!(
+            listing.properties,
+            ArchiveProperties {
+                archive_type: Some("Rar5".to_owned()),
+                solid: true,
+                multivolume: true,
+                volume_index: Some(0),
+                volumes: Some(2),
+                header_encrypted: false,
+                comment: Some("password: abc\nenjoy".to_owned()),
+            }
+        );
+        assert_eq!(listing.entries.len(), 5);
+        assert!(listing.entries[0].is_dir);
+        assert_eq!(
+            listing.entries[1],
+            ArchiveEntry {
+                path: PathBuf::from("docs/readme.txt"),
+                size: Some(12),
+                packed_size: Some(16),
+                modified: listed_time_to_utc(
+                    UNIX_EPOCH + Duration::new(1_709_210_096, 500_000_000)
+                ),
+                crc: Some(0xABCD),
+                attributes: None,
+                mode: None,
+                encrypted: true,
+                method: Some("AES-256:Deflate".to_owned()),
+                is_dir: false,
+                symlink_target: None,
+            }
+        );
+        assert_eq!(
+            listing.entries[2].symlink_target.as_deref(),
+            Some(Path::new("readme.txt"))
+        );
+        assert_eq!(listing.entries[3].mode, Some(0o2755));
+        assert!(listing.entries[4].is_dir);
+        assert_eq!(listing.entries[4].mode, Some(0o1777));
+        assert_eq!(listing.entries[4].symlink_target, None);
+        assert_eq!(
+            listing.smallest_encrypted_entry().as_deref(),
+            Some("docs/readme.txt")
+        );
+        assert_eq!(
+            parse_listed_time("1969-12-31 23:59:59"),
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(1))
+                .and_then(listed_time_to_utc)
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_listing_times_are_read_as_local_time() {
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn FileTimeToLocalFileTime(utc: *const FileTime, local: *mut FileTime) -> i32;
+        }
+
+        let utc = UNIX_EPOCH + Duration::new(1_709_210_096, 500_000_000);
+        let ticks = (utc.duration_since(UNIX_EPOCH).unwrap() + FILETIME_EPOCH_OFFSET).as_nanos()
+            as u64
+            / 100;
+        let mut local = FileTime { low: 0, high: 0 };
+        let utc_file_time = FileTime {
+            low: ticks as u32,
+            high: (ticks >> 32) as u32,
+        };
+        assert_ne!(
+            unsafe { FileTimeToLocalFileTime(&utc_file_time, &mut local) },
+            0
+        );
+
+        let ticks = u64::from(local.high) << 32 | u64::from(local.low);
+        let seconds = (ticks / 10_000_000) as i64 - FILETIME_EPOCH_OFFSET.as_secs() as i64;
+        let days = seconds.div_euclid(86_400) + 719_468;
+        let second_of_day = seconds.rem_euclid(86_400);
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        let listed = format!(
+            "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:07}",
+            second_of_day / 3_600,
+            second_of_day / 60 % 60,
+            second_of_day % 60,
+            ticks % 10_000_000
+        );
+
+        assert_eq!(parse_listed_time(&listed), Some(utc));
+    }
+}
@@ -0,0 +1,367 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::workflow::{numbered_file_name, rename_no_replace};
+
+pub trait SourceCleaner: Send + Sync {
+    fn clean(&self, sources: &[PathBuf]) -> Result<(), String>;
+}
+
+pub struct KeepCleaner;
+
+impl SourceCleaner for KeepCleaner {
+    fn clean(&self, _sources: &[PathBuf]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub struct TrashCleaner;
+
+impl SourceCleaner for TrashCleaner {
+    fn clean(&self, sources: &[PathBuf]) -> Result<(), String> {
+        trash::delete_all(sources).map_err(|error| error.to_string())
+    }
+}
+
+pub struct MoveToFolderCleaner {
+    folder: PathBuf,
+    mirror_root: Option<PathBuf>,
+}
+
+impl MoveToFolderCleaner {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Self {
+            folder: folder.into(),
+            mirror_root: None,
+        }
+    }
+
+    pub fn mirroring(mut self, root: impl Into<PathBuf>) -> Self {
+        self.mirror_root = Some(root.into());
+        self
+    }
+
+    fn destinations(&self, sources: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+        let mut placements = Vec::new();
+        for source in sources {
+            let name = source
+                .file_name()
+                .ok_or_else(|| format!("{} has no file name", source.display()))?;
+            let relative_parent = self
+                .mirror_root
+                .as_ref()
+                .and_then(|root| source.parent()?.strip_prefix(root).ok())
+                .unwrap_or(Path::new(""));
+            placements.push((self.folder.join(relative_parent), name));
+        }
+
+        for sequence in 0_u64.. {
+            let destinations: Vec<_> = placements
+                .iter()
+                .map(|(parent, name)| parent.join(numbered_file_name(name, sequence)))
+                .collect();
+            if destinations
+                .iter()
+                .all(|destination| fs::symlink_metadata(destination).is_err())
+            {
+                return Ok(destinations);
+            }
+        }
+
+        unreachable!("u64 destination sequence exhausted")
+    }
+}
+
+impl SourceCleaner for MoveToFolderCleaner {
+    fn clean(&self, sources: &[PathBuf]) -> Result<(), String> {
+        loop {
+            let destinations = self.destinations(sources)?;
+            if move_all(sources, &destinations)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+type Placement<'a> = (&'a Path, &'a Path, bool);
+
+fn move_all(sources: &[PathBuf], destinations: &[PathBuf]) -> Result<bool, String> {
+    let mut placed: Vec<Placement> = Vec::new();
+    for (source, destination) in sources.iter().zip(destinations) {
+        if let Some(parent) = destination.parent()
+            && let Err(error) = fs::create_dir_all(parent)
+        {
+            return Err(with_rollback(
+                format!("could not create {}: {error}", parent.display()),
+                roll_back(&placed),
+            ));
+        }
+        match place(source, destination) {
+            Ok(copied) => placed.push((source, destination, copied)),
+            Err(error) => {
+                let rollback = roll_back(&placed);
+                if error.kind() == io::ErrorKind::AlreadyExists && rollback.is_ok() {
+                    return Ok(false);
+                }
+                return Err(with_rollback(
+                    format!(
+                        "could not move {} to {}: {error}",
+                        source.display(),
+                        destination.display()
+                    ),
+                    rollback,
+                ));
+            }
+        }
+    }
+
+    for &(source, _, copied) in &placed {
+        if copied && let Err(error) = fs::remove_file(source) {
+            return Err(with_rollback(
+                format!("could not delete {}: {error}", source.display()),
+                roll_back(&placed),
+            ));
+        }
+    }
+    Ok(true)
+}
+
+fn roll_back(placed: &[Placement]) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for &(source, destination, _) in placed.iter().rev() {
+        let restored = if fs::symlink_metadata(source).is_ok() {
+            fs::remove_file(destination)
+        } else {
+            place(destination, source).and_then(|copied| {
+                if copied {
+                    fs::remove_file(destination)
+                } else {
+                    Ok(())
+                }
+            })
+        };
+        if let Err(error) = restored {
+            failures.push(format!(
+                "could not restore {} from {}: {error}",
+                source.display(),
+                destination.display()
+            ));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+fn with_rollback(message: String, rollback: Result<(), String>) -> String {
+    match rollback {
+        Ok(()) => message,
+        Err(rollback) => format!("{message}; rollback failed: {rollback}"),
+    }
+}
+
+fn place(source: &Path, destination: &Path) -> io::Result<bool> {
+    match rename_no_replace(source, destination) {
+        Ok(()) => Ok(false),
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+            copy_new(source, destination)?;
+            Ok(true)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+fn copy_new(source: &Path, destination: &Path) -> io::Result<()> {
+    let mut input = File::open(source)?;
+    let mut output = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+    io::copy(&mut input, &mut output)
+        .and_then(|_| output.set_permissions(input.metadata()?.permissions()))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(destination);
+        })
+}
+
+type DeleteConfirmation = Box<dyn Fn(&[PathBuf]) -> bool + Send + Sync>;
+
+pub struct DeleteCleaner {
+    confirm: DeleteConfirmation,
+}
+
+impl DeleteCleaner {
+    pub fn new(confirm: impl Fn(&[PathBuf]) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            confirm: Box::new(confirm),
+        }
+    }
+}
+
+impl SourceCleaner for DeleteCleaner {
+    fn clean(&self, sources: &[PathBuf]) -> Result<(), String> {
+        if !(self.confirm)(sources) {
+            return Ok(());
+        }
+        for source in sources {
+            fs::remove_file(source)
+                .map_err(|error| format!("could not delete {}: {error}", source.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn sources_are_moved_into_the_folder_or_kept() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let first = sandbox.path().join("release.7z.001");
+        let second = sandbox.path().join("release.7z.002");
+        fs::write(&first, b"first").unwrap();
+        fs::write(&second, b"second").unwrap();
+        let sources = [first.clone(), second.clone()];
+
+        KeepCleaner.clean(&sources).unwrap();
+        assert!(first.exists() && second.exists());
+
+        let processed = sandbox.path().join("processed");
+        fs::create_dir(&processed).unwrap();
+        fs::write(processed.join("release.7z.002"), b"earlier").unwrap();
+        MoveToFolderCleaner::new(&processed)
+            .clean(&sources)
+            .unwrap();
+        assert!(!first.exists() && !second.exists());
+        assert_eq!(
+            fs::read(processed.join("release.7z (1).001")).unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            fs::read(processed.join("release.7z (1).002")).unwrap(),
+            b"second"
+        );
+        assert_eq!(
+            fs::read(processed.join("release.7z.002")).unwrap(),
+            b"earlier"
+        );
+    }
+
+    #[test]
+    fn mirrored_moves_keep_the_relative_layout() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let downloads = sandbox.path().join("downloads");
+        let nested = downloads.join("show").join("season 1");
+        fs::create_dir_all(&nested).unwrap();
+        let archive = nested.join("episode.rar");
+        fs::write(&archive, b"Rar!").unwrap();
+
+        MoveToFolderCleaner::new(sandbox.path().join("processed"))
+            .mirroring(&downloads)
+            .clean(std::slice::from_ref(&archive))
+            .unwrap();
+
+        assert!(
+            sandbox
+                .path()
+                .join("processed/show/season 1/episode.rar")
+                .is_file()
+        );
+    }
+
+    #[test]
+    fn failed_moves_restore_every_volume() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let first = sandbox.path().join("release.part1.rar");
+        let missing = sandbox.path().join("release.part2.rar");
+        fs::write(&first, b"first").unwrap();
+        let processed = sandbox.path().join("processed");
+
+        assert!(
+            MoveToFolderCleaner::new(&processed)
+                .clean(&[first.clone(), missing])
+                .is_err()
+        );
+        assert_eq!(fs::read(&first).unwrap(), b"first");
+        assert!(!processed.join("release.part1.rar").exists());
+    }
+
+    #[test]
+    fn moves_never_replace_files_in_the_folder() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.zip");
+        fs::write(&archive, b"PK").unwrap();
+        let processed = sandbox.path().join("processed");
+        let taken = processed.join("release.zip");
+        fs::create_dir(&processed).unwrap();
+        fs::write(&taken, b"earlier").unwrap();
+
+        assert!(!move_all(std::slice::from_ref(&archive), std::slice::from_ref(&taken)).unwrap());
+        assert_eq!(fs::read(&archive).unwrap(), b"PK");
+        assert_eq!(fs::read(&taken).unwrap(), b"earlier");
+    }
+
+    #[test]
+    fn a_file_in_place_of_the_folder_fails_instead_of_retrying() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.zip");
+        fs::write(&archive, b"PK").unwrap();
+        let processed = sandbox.path().join("processed");
+        fs::write(&processed, b"not a folder").unwrap();
+
+        let error = MoveToFolderCleaner::new(&processed)
+            .clean(std::slice::from_ref(&archive))
+            .unwrap_err();
+
+        assert!(error.starts_with("could not create"), "{error}");
+        assert_eq!(fs::read(&archive).unwrap(), b"PK");
+    }
+
+    #[test]
+    fn rollback_removes_copies_and_reports_what_it_could_not_restore() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let copied_source = sandbox.path().join("release.part1.rar");
+        let copy = sandbox.path().join("copy.part1.rar");
+        fs::write(&copied_source, b"first").unwrap();
+        fs::write(&copy, b"first").unwrap();
+        let lost_source = sandbox.path().join("release.part2.rar");
+        let lost = sandbox.path().join("missing.part2.rar");
+
+        let error =
+            roll_back(&[(&copied_source, &copy, true), (&lost_source, &lost, false)]).unwrap_err();
+
+        assert!(!copy.exists());
+        assert_eq!(fs::read(&copied_source).unwrap(), b"first");
+        assert!(error.contains("could not restore"));
+        assert!(error.contains("release.part2.rar"));
+    }
+
+    #[test]
+    fn sources_are_deleted_only_after_confirmation() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.zip");
+        fs::write(&archive, b"PK").unwrap();
+        let sources = [archive.clone()];
+        let asked = Arc::new(AtomicBool::new(false));
+
+        let asked_by_cleaner = Arc::clone(&asked);
+        DeleteCleaner::new(move |sources| {
+            asked_by_cleaner.store(true, Ordering::SeqCst);
+            sources.len() != 1
+        })
+        .clean(&sources)
+        .unwrap();
+        assert!(asked.load(Ordering::SeqCst));
+        assert!(archive.exists());
+
+        DeleteCleaner::new(|_| true).clean(&sources).unwrap();
+        assert!(!archive.exists());
+    }
+}
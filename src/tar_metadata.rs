@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+const BLOCK_SIZE: u64 = 512;
+const XATTR_PREFIX: &str = "SCHILY.xattr.";
+const RESTORED_XATTR_NAMESPACE: &str = "user.";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PaxAttributes {
+    pub(crate) path: PathBuf,
+    pub(crate) xattrs: Vec<(String, Vec<u8>)>,
+}
+
+impl PaxAttributes {
+    pub(crate) fn relative_path(&self) -> Option<&Path> {
+        self.path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            .then_some(self.path.as_path())
+    }
+
+    pub(crate) fn restore(&self, path: &Path) -> io::Result<Vec<&str>> {
+        let mut skipped = Vec::new();
+        for (name, value) in &self.xattrs {
+            if name.starts_with(RESTORED_XATTR_NAMESPACE) {
+                set_xattr(path, name, value)?;
+            } else {
+                skipped.push(name.as_str());
+            }
+        }
+        Ok(skipped)
+    }
+}
+
+pub(crate) fn read_pax_attributes(tar: &Path) -> io::Result<Vec<PaxAttributes>> {
+    let mut reader = BufReader::new(File::open(tar)?);
+    let mut global = BTreeMap::new();
+    let mut local = BTreeMap::new();
+    let mut long_name = None;
+    let mut attributes = Vec::new();
+    let mut header = [0; BLOCK_SIZE as usize];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+        if header.iter().all(|&byte| byte == 0) {
+            break;
+        }
+
+        let size = header_size(&header)?;
+        match header[156] {
+            b'x' => local.extend(parse_records(&read_data(&mut reader, size)?)?),
+            b'g' => global.extend(parse_records(&read_data(&mut reader, size)?)?),
+            b'L' => {
+                let mut name = read_data(&mut reader, size)?;
+                name.truncate(
+                    name.iter()
+                        .position(|&byte| byte == 0)
+                        .unwrap_or(name.len()),
+                );
+                long_name = Some(name);
+            }
+            _ => {
+                let mut records = global.clone();
+                records.append(&mut local);
+                let size = match records.get("size") {
+                    Some(size) => parse_number(size)?,
+                    None => size,
+                };
+                let name = match records.get("path") {
+                    Some(path) => path.clone(),
+                    None => long_name.take().unwrap_or_else(|| header_name(&header)),
+                };
+                long_name = None;
+                skip_data(&mut reader, size)?;
+
+                let xattrs = records
+                    .into_iter()
+                    .filter(|(_, value)| !value.is_empty())
+                    .filter_map(|(key, value)| {
+                        Some((key.strip_prefix(XATTR_PREFIX)?.to_owned(), value))
+                    })
+                    .collect::<Vec<_>>();
+                if !xattrs.is_empty() {
+                    attributes.push(PaxAttributes {
+                        path: path_from_bytes(name),
+                        xattrs,
+                    });
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn header_size(header: &[u8]) -> io::Result<u64> {
+    let field = &header[124..136];
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7f), |size, &byte| {
+                size << 8 | u64::from(byte)
+            }));
+    }
+    let digits = field
+        .iter()
+        .copied()
+        .skip_while(|&byte| byte == b' ')
+        .take_while(|byte| byte.is_ascii_digit())
+        .collect::<Vec<_>>();
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(&String::from_utf8_lossy(&digits), 8).map_err(|_| invalid_header())
+}
+
+fn header_name(header: &[u8]) -> Vec<u8> {
+    let field = |range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        bytes[..bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(bytes.len())]
+            .to_vec()
+    };
+    let name = field(0..100);
+    let prefix = if &header[257..262] == b"ustar" {
+        field(345..500)
+    } else {
+        Vec::new()
+    };
+    if prefix.is_empty() {
+        name
+    } else {
+        [prefix.as_slice(), b"/", name.as_slice()].concat()
+    }
+}
+
+fn read_data(reader: &mut impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.by_ref().take(size).read_to_end(&mut data)?;
+    if data.len() as u64 != size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    skip_bytes(reader, size.next_multiple_of(BLOCK_SIZE) - size).map(|()| data)
+}
+
+fn skip_data(reader: &mut impl Read, size: u64) -> io::Result<()> {
+    skip_bytes(reader, size.next_multiple_of(BLOCK_SIZE))
+}
+
+fn skip_bytes(reader: &mut impl Read, count: u64) -> io::Result<()> {
+    if io::copy(&mut reader.by_ref().take(count), &mut io::sink())? != count {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn parse_records(mut data: &[u8]) -> io::Result<BTreeMap<String, Vec<u8>>> {
+    let mut records = BTreeMap::new();
+    while !data.is_empty() {
+        let space = data
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(invalid_header)?;
+        let length = parse_number(&data[..space])? as usize;
+        if length <= space + 1 || length > data.len() {
+            return Err(invalid_header());
+        }
+        let record = data[space + 1..length]
+            .strip_suffix(b"\n")
+            .ok_or_else(invalid_header)?;
+        let equals = record
+            .iter()
+            .position(|&byte| byte == b'=')
+            .ok_or_else(invalid_header)?;
+        records.insert(
+            String::from_utf8_lossy(&record[..equals]).into_owned(),
+            record[equals + 1..].to_vec(),
+        );
+        data = &data[length..];
+    }
+    Ok(records)
+}
+
+fn parse_number(value: &[u8]) -> io::Result<u64> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(invalid_header)
+}
+
+fn invalid_header() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed tar extended header")
+}
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn setxattr(
+        path: *const std::ffi::c_char,
+        name: *const std::ffi::c_char,
+        value: *const std::ffi::c_void,
+        size: usize,
+        position: u32,
+        options: std::ffi::c_int,
+    ) -> std::ffi::c_int;
+}
+
+#[cfg(target_os = "macos")]
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    const XATTR_NOFOLLOW: std::ffi::c_int = 0x0001;
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    let result = unsafe {
+        setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+            XATTR_NOFOLLOW,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extended attributes cannot be restored on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_member(name: &str, typeflag: u8, data: &[u8]) -> Vec<u8> {
+        let mut header = [0_u8; BLOCK_SIZE as usize];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].fill(b' ');
+        let checksum = header.iter().map(|&byte| u32::from(byte)).sum::<u32>();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+        let mut member = header.to_vec();
+        member.extend(data);
+        member.resize(member.len().next_multiple_of(BLOCK_SIZE as usize), 0);
+        member
+    }
+
+    fn pax_records(records: &[(&str, &str)]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        for (key, value) in records {
+            let body = format!(" {key}={value}\n");
+            let mut length = body.len() + 1;
+            while length.to_string().len() + body.len() != length {
+                length += 1;
+            }
+            encoded.extend(format!("{length}{body}").into_bytes());
+        }
+        encoded
+    }
+
+    #[test]
+    fn pax_headers_yield_xattrs_for_their_entries() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let tar = sandbox.path().join("scripts.tar");
+        let mut archive = tar_member(
+            "pax_global_header",
+            b'g',
+            &pax_records(&[("SCHILY.xattr.user.origin", "ezz")]),
+        );
+        archive.extend(tar_member(
+            "PaxHeaders/run.sh",
+            b'x',
+            &pax_records(&[
+                ("path", "scripts/run.sh"),
+                ("SCHILY.xattr.user.checksum", "abc"),
+                ("SCHILY.acl.access", "user::rwx,group::r-x,other::r--"),
+            ]),
+        ));
+        archive.extend(tar_member("run.sh", b'0', b"#!/bin/sh\n"));
+        archive.extend(tar_member("scripts/plain.txt", b'0', b"plain"));
+        archive.extend([0; 2 * BLOCK_SIZE as usize]);
+        std::fs::write(&tar, archive).unwrap();
+
+        let attributes = read_pax_attributes(&tar).expect("read pax headers");
+
+        assert_eq!(
+            attributes,
+            [
+                PaxAttributes {
+                    path: PathBuf::from("scripts/run.sh"),
+                    xattrs: vec![
+                        ("user.checksum".to_owned(), b"abc".to_vec()),
+                        ("user.origin".to_owned(), b"ezz".to_vec()),
+                    ],
+                },
+                PaxAttributes {
+                    path: PathBuf::from("scripts/plain.txt"),
+                    xattrs: vec![("user.origin".to_owned(), b"ezz".to_vec())],
+                },
+            ]
+        );
+        assert_eq!(
+            PaxAttributes {
+                path: PathBuf::from("../escape"),
+                ..Default::default()
+            }
+            .relative_path(),
+            None
+        );
+    }
+
+    #[test]
+    fn only_user_namespace_xattrs_are_restored() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let file = sandbox.path().join("download.bin");
+        std::fs::write(&file, b"payload").unwrap();
+        let attributes = PaxAttributes {
+            path: PathBuf::from("download.bin"),
+            xattrs: vec![
+                (
+                    "com.apple.quarantine".to_owned(),
+                    b"0081;00000000;;".to_vec(),
+                ),
+                ("security.capability".to_owned(), vec![1, 0, 0, 2]),
+            ],
+        };
+
+        let skipped = attributes.restore(&file).expect("skip foreign namespaces");
+
+        assert_eq!(skipped, ["com.apple.quarantine", "security.capability"]);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn restored_xattrs_can_be_read_back() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        unsafe extern "C" {
+            fn getxattr(
+                path: *const std::ffi::c_char,
+                name: *const std::ffi::c_char,
+                value: *mut std::ffi::c_void,
+                size: usize,
+                position: u32,
+                options: std::ffi::c_int,
+            ) -> isize;
+        }
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let script = sandbox.path().join("run.sh");
+        std::fs::write(&script, b"#!/bin/sh\n").unwrap();
+
+        let attributes = PaxAttributes {
+            path: PathBuf::from("run.sh"),
+            xattrs: vec![("user.checksum".to_owned(), b"abc".to_vec())],
+        };
+        let skipped = attributes
+            .restore(&script)
+            .expect("restore extended attributes");
+        assert!(skipped.is_empty());
+
+        let path = CString::new(script.as_os_str().as_bytes()).unwrap();
+        let name = CString::new("user.checksum").unwrap();
+        let mut value = [0_u8; 16];
+        let length = unsafe {
+            getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            )
+        };
+        assert_eq!(usize::try_from(length).ok(), Some(3));
+        assert_eq!(&value[..3], b"abc");
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn xattrs_report_unsupported_platforms() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let file = sandbox.path().join("run.sh");
+        std::fs::write(&file, b"#!/bin/sh\n").unwrap();
+
+        let error = PaxAttributes {
+            path: PathBuf::from("run.sh"),
+            xattrs: vec![("user.checksum".to_owned(), b"abc".to_vec())],
+        }
+        .restore(&file)
+        .expect_err("xattrs are only restored on macOS");
+
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+}
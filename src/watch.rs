@@ -0,0 +1,370 @@
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::warn;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use thiserror::Error;
+
+use crate::ExtractionError;
+
+const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(5);
+const IGNORE_PERIOD: Duration = Duration::from_secs(60);
+const IN_PROGRESS_EXTENSIONS: &[&str] = &[
+    "part",
+    "partial",
+    "crdownload",
+    "download",
+    "opdownload",
+    "tmp",
+    "!qb",
+    "!ut",
+    "aria2",
+];
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum WatchError {
+    #[error("Watched directory does not exist: {0}")]
+    DirectoryNotFound(PathBuf),
+
+    #[error("Could not watch {path}: {message}")]
+    Watch { path: PathBuf, message: String },
+
+    #[error("File system watcher stopped unexpectedly")]
+    Disconnected,
+
+    #[error("Could not probe new files: {0}")]
+    Engine(ExtractionError),
+}
+
+pub struct FolderWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    tracker: StabilityTracker,
+}
+
+impl FolderWatcher {
+    pub fn new<I, P>(directories: I, recursive: bool) -> Result<Self, WatchError>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).map_err(|error| WatchError::Watch {
+                path: PathBuf::new(),
+                message: error.to_string(),
+            })?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        for directory in directories {
+            let directory = directory.into();
+            if !directory.is_dir() {
+                return Err(WatchError::DirectoryNotFound(directory));
+            }
+            watcher
+                .watch(&directory, mode)
+                .map_err(|error| WatchError::Watch {
+                    path: directory,
+                    message: error.to_string(),
+                })?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            tracker: StabilityTracker::new(DEFAULT_SETTLE_TIME),
+        })
+    }
+
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.tracker.settle_time = settle_time;
+        self
+    }
+
+    pub fn next_batch(&mut self) -> Result<Vec<PathBuf>, WatchError> {
+        loop {
+            let now = Instant::now();
+            let ready = self.tracker.take_ready(now);
+            if !ready.is_empty() {
+                return Ok(ready);
+            }
+
+            let event = match self.tracker.next_check(now) {
+                Some(timeout) => match self.events.recv_timeout(timeout) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return Err(WatchError::Disconnected),
+                },
+                None => Some(self.events.recv().map_err(|_| WatchError::Disconnected)?),
+            };
+            match event {
+                Some(Ok(event)) => {
+                    let now = Instant::now();
+                    for path in event.paths {
+                        self.tracker.observe(path, now);
+                    }
+                }
+                Some(Err(error)) => warn!("file system watcher reported an error: {error}"),
+                None => {}
+            }
+        }
+    }
+
+    pub fn ignore(&mut self, outputs: impl IntoIterator<Item = PathBuf>) {
+        self.tracker.ignore(outputs, Instant::now());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observation {
+    size: u64,
+    modified: Option<SystemTime>,
+    changed_at: Instant,
+}
+
+struct StabilityTracker {
+    settle_time: Duration,
+    pending: BTreeMap<PathBuf, Observation>,
+    ignored: Vec<(PathBuf, Instant)>,
+}
+
+impl StabilityTracker {
+    fn new(settle_time: Duration) -> Self {
+        Self {
+            settle_time,
+            pending: BTreeMap::new(),
+            ignored: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, path: PathBuf, now: Instant) {
+        if is_workspace_path(&path) || self.is_ignored(&path, now) {
+            return;
+        }
+        self.refresh(path, now);
+    }
+
+    fn refresh(&mut self, path: PathBuf, now: Instant) {
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => {
+                self.pending.remove(&path);
+                return;
+            }
+        };
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+        match self.pending.get_mut(&path) {
+            Some(observation) if observation.size == size && observation.modified == modified => {}
+            Some(observation) => {
+                observation.size = size;
+                observation.modified = modified;
+                observation.changed_at = now;
+            }
+            None => {
+                self.pending.insert(
+                    path,
+                    Observation {
+                        size,
+                        modified,
+                        changed_at: now,
+                    },
+                );
+            }
+        }
+    }
+
+    fn take_ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let paths: Vec<_> = self.pending.keys().cloned().collect();
+        for path in paths {
+            self.refresh(path, now);
+        }
+
+        let mut busy_directories = Vec::new();
+        for (path, observation) in &self.pending {
+            if now.duration_since(observation.changed_at) < self.settle_time {
+                busy_directories.push(path.parent().map(Path::to_path_buf));
+            }
+        }
+
+        let ready: Vec<_> = self
+            .pending
+            .keys()
+            .filter(|path| !busy_directories.contains(&path.parent().map(Path::to_path_buf)))
+            .filter(|path| !is_download_in_progress(path))
+            .cloned()
+            .collect();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+
+    fn next_check(&self, now: Instant) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let timeout = self
+            .pending
+            .values()
+            .map(|observation| {
+                (observation.changed_at + self.settle_time).saturating_duration_since(now)
+            })
+            .filter(|remaining| !remaining.is_zero())
+            .min()
+            .unwrap_or(self.settle_time);
+        Some(timeout)
+    }
+
+    fn ignore(&mut self, outputs: impl IntoIterator<Item = PathBuf>, now: Instant) {
+        for output in outputs {
+            self.pending.retain(|path, _| !path.starts_with(&output));
+            self.ignored.push((output, now));
+        }
+    }
+
+    fn is_ignored(&mut self, path: &Path, now: Instant) -> bool {
+        self.ignored
+            .retain(|(_, ignored_at)| now.duration_since(*ignored_at) < IGNORE_PERIOD);
+        self.ignored
+            .iter()
+            .any(|(output, _)| path.starts_with(output))
+    }
+}
+
+fn is_workspace_path(path: &Path) -> bool {
+    path.components().any(|component| {
+        component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|name| name.starts_with(".ezz-work-"))
+    })
+}
+
+fn is_download_in_progress(path: &Path) -> bool {
+    let in_progress_extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| {
+            IN_PROGRESS_EXTENSIONS
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        });
+    let mut control_file = path.as_os_str().to_owned();
+    control_file.push(".aria2");
+    in_progress_extension || Path::new(&control_file).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_released_only_after_their_directory_settles() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let first = sandbox.path().join("release.part1.rar");
+        let second = sandbox.path().join("release.part2.rar");
+        fs::write(&first, b"first volume").unwrap();
+        fs::write(&second, b"second").unwrap();
+        let start = Instant::now();
+        let mut tracker = StabilityTracker::new(Duration::from_secs(5));
+
+        tracker.observe(first.clone(), start);
+        tracker.observe(second.clone(), start + Duration::from_secs(3));
+
+        assert!(
+            tracker
+                .take_ready(start + Duration::from_secs(6))
+                .is_empty()
+        );
+        assert_eq!(
+            tracker.take_ready(start + Duration::from_secs(8)),
+            vec![first, second]
+        );
+        assert_eq!(tracker.next_check(start + Duration::from_secs(8)), None);
+    }
+
+    #[test]
+    fn growing_files_restart_the_settle_time() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let archive = sandbox.path().join("release.zip");
+        fs::write(&archive, b"PK").unwrap();
+        let start = Instant::now();
+        let mut tracker = StabilityTracker::new(Duration::from_secs(5));
+
+        tracker.observe(archive.clone(), start);
+        fs::write(&archive, b"PK\x03\x04 more data").unwrap();
+
+        assert!(
+            tracker
+                .take_ready(start + Duration::from_secs(6))
+                .is_empty()
+        );
+        assert_eq!(
+            tracker.take_ready(start + Duration::from_secs(11)),
+            vec![archive]
+        );
+    }
+
+    #[test]
+    fn download_in_progress_names_are_never_released() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let partial = sandbox.path().join("release.zip.crdownload");
+        let aria2 = sandbox.path().join("movie.7z");
+        fs::write(&partial, b"partial").unwrap();
+        fs::write(&aria2, b"partial").unwrap();
+        fs::write(sandbox.path().join("movie.7z.aria2"), b"control").unwrap();
+        let start = Instant::now();
+        let mut tracker = StabilityTracker::new(Duration::from_secs(5));
+
+        tracker.observe(partial.clone(), start);
+        tracker.observe(aria2.clone(), start);
+
+        assert!(
+            tracker
+                .take_ready(start + Duration::from_secs(60))
+                .is_empty()
+        );
+
+        let completed = sandbox.path().join("release.zip");
+        fs::rename(&partial, &completed).unwrap();
+        tracker.observe(partial, start + Duration::from_secs(61));
+        tracker.observe(completed.clone(), start + Duration::from_secs(61));
+
+        assert_eq!(
+            tracker.take_ready(start + Duration::from_secs(70)),
+            vec![completed]
+        );
+    }
+
+    #[test]
+    fn committed_outputs_and_workspaces_are_ignored() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let output = sandbox.path().join("release");
+        let workspace = sandbox.path().join(".ezz-work-abc");
+        fs::create_dir(&output).unwrap();
+        fs::create_dir(&workspace).unwrap();
+        fs::write(output.join("inner.zip"), b"PK").unwrap();
+        fs::write(workspace.join("extracted.zip"), b"PK").unwrap();
+        let start = Instant::now();
+        let mut tracker = StabilityTracker::new(Duration::from_secs(5));
+
+        tracker.ignore([output.clone()], start);
+        tracker.observe(output.join("inner.zip"), start);
+        tracker.observe(workspace.join("extracted.zip"), start);
+
+        assert_eq!(tracker.next_check(start), None);
+        assert!(
+            tracker
+                .take_ready(start + Duration::from_secs(10))
+                .is_empty()
+        );
+    }
+}
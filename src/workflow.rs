@@ -1,20 +1,114 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
+use log::warn;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
-use crate::password_store::PasswordStore;
-use crate::seven_zip::SevenZip;
+use crate::carrier::CarrierFormat;
+use crate::password_hints::PasswordHints;
+use crate::password_providers::{
+    PasswordCandidate, PasswordCandidateProvider, PasswordRequest, StoredPasswords,
+};
+use crate::password_store::{
+    ArchiveFingerprint, JsonFileBackend, PasswordDatabaseKey, PasswordRetention, PasswordStore,
+};
+use crate::seven_zip::{
+    ArchiveListing, ArchiveProperties, EmbeddedArchive, PasswordTest, SevenZip,
+};
+use crate::source_cleaner::{SourceCleaner, TrashCleaner};
+use crate::tar_metadata::read_pax_attributes;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExtractionOutcome {
     pub input: PathBuf,
     pub output: PathBuf,
+    pub additional_outputs: Vec<PathBuf>,
+    pub password_sources: Vec<String>,
+    pub comments: Vec<String>,
     pub warnings: Vec<ExtractionWarning>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrySelection {
+    pub entries: Vec<String>,
+    pub clean_source: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub input: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub password_sources: Vec<String>,
+    pub problems: Vec<VerificationProblem>,
+}
+
+impl VerificationReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationProblem {
+    CrcError { entry: PathBuf },
+    DataError { entry: PathBuf },
+    MissingVolume { volume: PathBuf },
+    UnexpectedEnd,
+    DataAfterEnd,
+    Other { message: String },
+}
+
+impl fmt::Display for VerificationProblem {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CrcError { entry } => write!(formatter, "CRC error in {}", entry.display()),
+            Self::DataError { entry } => write!(formatter, "data error in {}", entry.display()),
+            Self::MissingVolume { volume } => {
+                write!(formatter, "missing volume {}", volume.display())
+            }
+            Self::UnexpectedEnd => formatter.write_str("unexpected end of archive"),
+            Self::DataAfterEnd => formatter.write_str("unexpected data after the end of archive"),
+            Self::Other { message } => formatter.write_str(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractionPlan {
+    pub input: PathBuf,
+    pub archive_set: ArchiveSet,
+    pub format: InputFormat,
+    pub outputs: Vec<PlannedOutput>,
+    pub sources_to_clean: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputFormat {
+    Archive,
+    AppendedArchive { payloads: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedOutput {
+    pub password_required: bool,
+    pub listing: Option<ArchiveListing>,
+    pub layout: Option<PlannedLayout>,
+    pub destination: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedLayout {
+    SingleItem,
+    Folder,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExtractionWarning {
     SourceCleanupFailed {
@@ -25,6 +119,14 @@ pub enum ExtractionWarning {
         path: PathBuf,
         message: String,
     },
+    RecursiveExtractionStopped {
+        archive: PathBuf,
+        reason: String,
+    },
+    MetadataNotRestored {
+        path: PathBuf,
+        message: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -34,12 +136,22 @@ pub struct PasswordResponse {
     pub keep_original: bool,
 }
 
-pub trait PasswordPrompt {
+pub trait PasswordPrompt: Send + Sync {
     fn request_password(
         &self,
         input: &Path,
         previous_attempt_failed: bool,
+        comment: Option<&str>,
     ) -> Option<PasswordResponse>;
+
+    fn request_database_passphrase(
+        &self,
+        _database: &Path,
+        _previous_attempt_failed: bool,
+        _new_passphrase: bool,
+    ) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
@@ -50,6 +162,9 @@ pub enum ExtractionError {
     #[error("Input is not a file: {0}")]
     InputNotFile(PathBuf),
 
+    #[error("No archive entries were selected in {0}")]
+    EmptySelection(PathBuf),
+
     #[error("7-Zip executable does not exist: {0}")]
     EngineNotFound(PathBuf),
 
@@ -78,6 +193,12 @@ pub enum ExtractionError {
     #[error("Could not read password database {path}: {message}")]
     PasswordStore { path: PathBuf, message: String },
 
+    #[error("Password database {path} is locked: {message}")]
+    PasswordStoreLocked { path: PathBuf, message: String },
+
+    #[error("Password provider {provider} failed: {message}")]
+    PasswordProvider { provider: String, message: String },
+
     #[error("Archive produced no output: {0}")]
     EmptyArchive(PathBuf),
 
@@ -97,29 +218,159 @@ pub struct ExtractionWorkflow {
     source_cleaner: Box<dyn SourceCleaner>,
     password_prompt: Box<dyn PasswordPrompt>,
     password_store: Option<PasswordStore>,
+    password_providers: Vec<Box<dyn PasswordCandidateProvider>>,
+    recursion_depth: usize,
+    comment_files: bool,
+    extended_attributes: bool,
+}
+
+pub struct ExtractionWorkflowBuilder {
+    seven_zip: PathBuf,
+    source_cleaner: Box<dyn SourceCleaner>,
+    password_prompt: Box<dyn PasswordPrompt>,
+    password_database: Option<PathBuf>,
+    password_database_key: Option<PasswordDatabaseKey>,
+    password_retention: PasswordRetention,
+    password_providers: Vec<Box<dyn PasswordCandidateProvider>>,
+    recursion_depth: usize,
+    comment_files: bool,
+    extended_attributes: bool,
+}
+
+impl ExtractionWorkflowBuilder {
+    pub fn source_cleaner(mut self, source_cleaner: impl SourceCleaner + 'static) -> Self {
+        self.source_cleaner = Box::new(source_cleaner);
+        self
+    }
+
+    pub fn password_prompt(mut self, password_prompt: impl PasswordPrompt + 'static) -> Self {
+        self.password_prompt = Box::new(password_prompt);
+        self
+    }
+
+    pub fn password_database(mut self, path: impl Into<PathBuf>) -> Self {
+        self.password_database = Some(path.into());
+        self
+    }
+
+    pub fn password_database_key(mut self, key: PasswordDatabaseKey) -> Self {
+        self.password_database_key = Some(key);
+        self
+    }
+
+    pub fn password_retention(mut self, retention: PasswordRetention) -> Self {
+        self.password_retention = retention;
+        self
+    }
+
+    pub fn password_providers(
+        mut self,
+        providers: Vec<Box<dyn PasswordCandidateProvider>>,
+    ) -> Self {
+        self.password_providers = providers;
+        self
+    }
+
+    pub fn recursive_extraction(mut self, max_depth: usize) -> Self {
+        self.recursion_depth = max_depth;
+        self
+    }
+
+    pub fn comment_files(mut self, enabled: bool) -> Self {
+        self.comment_files = enabled;
+        self
+    }
+
+    pub fn extended_attributes(mut self, enabled: bool) -> Self {
+        self.extended_attributes = enabled;
+        self
+    }
+
+    pub fn build(self) -> ExtractionWorkflow {
+        let password_store = self.password_database.map(|path| {
+            let mut backend = JsonFileBackend::new(path);
+            if let Some(key) = self.password_database_key {
+                backend = backend.with_key(key);
+            }
+            PasswordStore::new(backend).with_retention(self.password_retention)
+        });
+        ExtractionWorkflow {
+            seven_zip: self.seven_zip,
+            source_cleaner: self.source_cleaner,
+            password_prompt: self.password_prompt,
+            password_store,
+            password_providers: self.password_providers,
+            recursion_depth: self.recursion_depth,
+            comment_files: self.comment_files,
+            extended_attributes: self.extended_attributes,
+        }
+    }
 }
 
 impl ExtractionWorkflow {
-    pub fn new(seven_zip: impl Into<PathBuf>) -> Self {
-        Self {
+    pub fn builder(seven_zip: impl Into<PathBuf>) -> ExtractionWorkflowBuilder {
+        ExtractionWorkflowBuilder {
             seven_zip: seven_zip.into(),
             source_cleaner: Box::new(TrashCleaner),
             password_prompt: Box::new(NoPasswordPrompt),
-            password_store: None,
+            password_database: None,
+            password_database_key: None,
+            password_retention: PasswordRetention::default(),
+            password_providers: default_password_providers(),
+            recursion_depth: 0,
+            comment_files: false,
+            extended_attributes: false,
         }
     }
 
+    pub fn new(seven_zip: impl Into<PathBuf>) -> Self {
+        Self::builder(seven_zip).build()
+    }
+
     pub fn with_password_support(
         seven_zip: impl Into<PathBuf>,
         password_store: impl Into<PathBuf>,
         password_prompt: impl PasswordPrompt + 'static,
     ) -> Self {
-        Self {
-            seven_zip: seven_zip.into(),
-            source_cleaner: Box::new(TrashCleaner),
-            password_prompt: Box::new(password_prompt),
-            password_store: Some(PasswordStore::new(password_store)),
-        }
+        Self::builder(seven_zip)
+            .password_database(password_store)
+            .password_prompt(password_prompt)
+            .build()
+    }
+
+    pub fn with_password_database_key(mut self, key: PasswordDatabaseKey) -> Self {
+        self.password_store = self.password_store.map(|store| store.with_key(key));
+        self
+    }
+
+    pub fn with_password_retention(mut self, retention: PasswordRetention) -> Self {
+        self.password_store = self
+            .password_store
+            .map(|store| store.with_retention(retention));
+        self
+    }
+
+    pub fn with_password_providers(
+        mut self,
+        providers: Vec<Box<dyn PasswordCandidateProvider>>,
+    ) -> Self {
+        self.password_providers = providers;
+        self
+    }
+
+    pub fn with_recursive_extraction(mut self, max_depth: usize) -> Self {
+        self.recursion_depth = max_depth;
+        self
+    }
+
+    pub fn with_comment_files(mut self, enabled: bool) -> Self {
+        self.comment_files = enabled;
+        self
+    }
+
+    pub fn with_extended_attributes(mut self, enabled: bool) -> Self {
+        self.extended_attributes = enabled;
+        self
     }
 
     #[cfg(test)]
@@ -127,12 +378,9 @@ impl ExtractionWorkflow {
         seven_zip: impl Into<PathBuf>,
         source_cleaner: impl SourceCleaner + 'static,
     ) -> Self {
-        Self {
-            seven_zip: seven_zip.into(),
-            source_cleaner: Box::new(source_cleaner),
-            password_prompt: Box::new(NoPasswordPrompt),
-            password_store: None,
-        }
+        Self::builder(seven_zip)
+            .source_cleaner(source_cleaner)
+            .build()
     }
 
     #[cfg(test)]
@@ -141,12 +389,10 @@ impl ExtractionWorkflow {
         source_cleaner: impl SourceCleaner + 'static,
         password_prompt: impl PasswordPrompt + 'static,
     ) -> Self {
-        Self {
-            seven_zip: seven_zip.into(),
-            source_cleaner: Box::new(source_cleaner),
-            password_prompt: Box::new(password_prompt),
-            password_store: None,
-        }
+        Self::builder(seven_zip)
+            .source_cleaner(source_cleaner)
+            .password_prompt(password_prompt)
+            .build()
     }
 
     #[cfg(test)]
@@ -156,26 +402,44 @@ impl ExtractionWorkflow {
         password_prompt: impl PasswordPrompt + 'static,
         password_store: impl Into<PathBuf>,
     ) -> Self {
-        Self {
-            seven_zip: seven_zip.into(),
-            source_cleaner: Box::new(source_cleaner),
-            password_prompt: Box::new(password_prompt),
-            password_store: Some(PasswordStore::new(password_store)),
-        }
+        Self::builder(seven_zip)
+            .source_cleaner(source_cleaner)
+            .password_prompt(password_prompt)
+            .password_database(password_store)
+            .build()
     }
 
     pub fn extract(&self, input: impl AsRef<Path>) -> Result<ExtractionOutcome, ExtractionError> {
+        self.extract_with_prompt(input.as_ref(), self.password_prompt.as_ref())
+    }
+
+    pub fn extract_selected(
+        &self,
+        input: impl AsRef<Path>,
+        selection: &EntrySelection,
+    ) -> Result<ExtractionOutcome, ExtractionError> {
         let input = input.as_ref();
-        if !input.exists() {
-            return Err(ExtractionError::InputNotFound(input.to_path_buf()));
-        }
-        if !input.is_file() {
-            return Err(ExtractionError::InputNotFile(input.to_path_buf()));
-        }
-        if !self.seven_zip.is_file() {
-            return Err(ExtractionError::EngineNotFound(self.seven_zip.clone()));
+        if selection.entries.is_empty() {
+            return Err(ExtractionError::EmptySelection(input.to_path_buf()));
         }
+        self.run_extraction(input, self.password_prompt.as_ref(), Some(selection))
+    }
 
+    pub(crate) fn extract_with_prompt(
+        &self,
+        input: &Path,
+        password_prompt: &dyn PasswordPrompt,
+    ) -> Result<ExtractionOutcome, ExtractionError> {
+        self.run_extraction(input, password_prompt, None)
+    }
+
+    fn run_extraction(
+        &self,
+        input: &Path,
+        password_prompt: &dyn PasswordPrompt,
+        selection: Option<&EntrySelection>,
+    ) -> Result<ExtractionOutcome, ExtractionError> {
+        self.check_input(input)?;
         let selected_input = absolute_path(input)?;
         let archive_set = resolve_archive_set(&selected_input)?;
         let input = &archive_set.primary;
@@ -191,31 +455,85 @@ impl ExtractionWorkflow {
             .prefix(".ezz-work-")
             .tempdir_in(parent)
             .map_err(|error| file_system_error("create workspace for", input, error))?;
-        let extracted = workspace.path().join("extracted");
-        fs::create_dir(&extracted)
-            .map_err(|error| file_system_error("create extraction directory", &extracted, error))?;
 
         let prepared = workspace.path().join("prepared");
-        let archive_input = input_format.prepare(&seven_zip, input, &prepared)?;
-        let password = self.resolve_password(&seven_zip, &archive_input, &selected_input)?;
-        seven_zip.validate_paths(&archive_input, &password.value)?;
+        let archive_inputs = input_format.prepare(&seven_zip, input, &prepared)?;
+        let mut passwords = Vec::new();
+        let mut warnings = Vec::new();
+        let mut extracted_outputs = Vec::new();
+        let mut comments = Vec::new();
+        let mut directory_modes = Vec::new();
+        for (index, archive_input) in archive_inputs.iter().enumerate() {
+            let extracted = workspace.path().join(format!("extracted-{index}"));
+            let password = self.resolve_password(
+                &seven_zip,
+                password_prompt,
+                archive_input,
+                &selected_input,
+                input_directory(&selected_input),
+                &archive_set.output_stem,
+            )?;
+            let listing = extract_resolved(
+                &seven_zip,
+                archive_input,
+                &password.value,
+                &extracted,
+                selection.map_or(&[], |selection| &selection.entries),
+            )?;
+            passwords.push(password);
+            directory_modes.extend(self.restore_metadata(
+                archive_input,
+                &listing,
+                &extracted,
+                &mut warnings,
+            ));
+            let extracted = self.extract_nested_archives(
+                &selected_input,
+                password_prompt,
+                extracted,
+                &mut passwords,
+                &mut directory_modes,
+                &mut warnings,
+            )?;
+            if let Some(comment) = listing.properties.comment {
+                if self.comment_files {
+                    write_comment_file(&extracted, &comment)?;
+                }
+                comments.push(comment);
+            }
+            extracted_outputs.push(extracted);
+        }
 
-        seven_zip.extract(&archive_input, &extracted, &password.value)?;
-        validate_extracted_output(&extracted)?;
-        let output = commit_output(input, &extracted, &archive_set.output_stem)?;
+        let staged = extracted_outputs
+            .iter()
+            .map(|extracted| stage_output(input, extracted, &archive_set.output_stem))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = commit_outputs(input, &staged)?;
+        warnings.extend(apply_directory_modes(&directory_modes, &staged, &outputs));
+        let mut outputs = outputs.into_iter();
+        let output = outputs.next().expect("at least one prepared archive input");
         let sources = archive_set.sources;
-        let mut warnings = Vec::new();
-        if password.remember
-            && !password.value.is_empty()
-            && let Some(store) = &self.password_store
-            && let Err(message) = store.record_success(&password.value)
-        {
-            warnings.push(ExtractionWarning::PasswordStoreUpdateFailed {
-                path: store.path().to_path_buf(),
-                message,
-            });
+        if let Some(store) = &self.password_store {
+            for password in &passwords {
+                let succeeded = (password.remember && !password.value.is_empty())
+                    .then_some(password.value.as_str());
+                if (!password.failed.is_empty() || succeeded.is_some())
+                    && let Err(error) = store.record_attempts(
+                        &password.failed,
+                        succeeded,
+                        password.fingerprint.as_ref(),
+                        password_prompt,
+                    )
+                {
+                    warnings.push(ExtractionWarning::PasswordStoreUpdateFailed {
+                        path: store.path().to_path_buf(),
+                        message: error.to_string(),
+                    });
+                }
+            }
         }
-        if !password.keep_original
+        if selection.is_none_or(|selection| selection.clean_source)
+            && !passwords.iter().any(|password| password.keep_original)
             && let Some(message) = self.source_cleaner.clean(&sources).err()
         {
             warnings.push(ExtractionWarning::SourceCleanupFailed { sources, message });
@@ -224,15 +542,344 @@ impl ExtractionWorkflow {
         Ok(ExtractionOutcome {
             input: selected_input,
             output,
+            additional_outputs: outputs.collect(),
+            password_sources: passwords
+                .iter()
+                .filter_map(|password| password.source.clone())
+                .collect(),
+            comments,
             warnings,
         })
     }
 
+    pub fn plan(&self, input: impl AsRef<Path>) -> Result<ExtractionPlan, ExtractionError> {
+        let input = input.as_ref();
+        self.check_input(input)?;
+        let selected_input = absolute_path(input)?;
+        let archive_set = resolve_archive_set(&selected_input)?;
+        let primary = &archive_set.primary;
+        let parent = primary.parent().expect("absolute input parent");
+        let seven_zip = SevenZip::new(&self.seven_zip);
+        if let DetectedInputFormat::AppendedArchive { embedded } =
+            detect_input_format(&seven_zip, primary)?
+        {
+            return Ok(ExtractionPlan {
+                input: selected_input,
+                sources_to_clean: archive_set.sources.clone(),
+                archive_set,
+                format: InputFormat::AppendedArchive {
+                    payloads: embedded.len(),
+                },
+                outputs: embedded
+                    .iter()
+                    .map(|_| PlannedOutput {
+                        password_required: true,
+                        listing: None,
+                        layout: None,
+                        destination: None,
+                    })
+                    .collect(),
+            });
+        }
+
+        let output = match seven_zip.list(primary, "") {
+            Ok(listing) => {
+                let comment_file = self.comment_files && listing.properties.comment.is_some();
+                let (layout, destination) =
+                    plan_layout(&listing, parent, &archive_set.output_stem, comment_file)
+                        .ok_or_else(|| ExtractionError::EmptyArchive(primary.clone()))?;
+                PlannedOutput {
+                    password_required: listing.entries.iter().any(|entry| entry.encrypted),
+                    listing: Some(listing),
+                    layout: Some(layout),
+                    destination: Some(destination),
+                }
+            }
+            Err(ExtractionError::WrongPassword) => PlannedOutput {
+                password_required: true,
+                listing: None,
+                layout: None,
+                destination: None,
+            },
+            Err(error) => return Err(error),
+        };
+
+        Ok(ExtractionPlan {
+            input: selected_input,
+            sources_to_clean: archive_set.sources.clone(),
+            archive_set,
+            format: InputFormat::Archive,
+            outputs: vec![output],
+        })
+    }
+
+    pub fn list(&self, input: impl AsRef<Path>) -> Result<Vec<ArchiveListing>, ExtractionError> {
+        let input = input.as_ref();
+        self.check_input(input)?;
+        let selected_input = absolute_path(input)?;
+        let archive_set = resolve_archive_set(&selected_input)?;
+        let seven_zip = SevenZip::new(&self.seven_zip);
+        let (archive_inputs, _staging) = listing_inputs(&seven_zip, &archive_set.primary)?;
+
+        archive_inputs
+            .iter()
+            .map(|archive_input| match seven_zip.list(archive_input, "") {
+                Err(ExtractionError::WrongPassword) => {
+                    let password = self.resolve_password(
+                        &seven_zip,
+                        self.password_prompt.as_ref(),
+                        archive_input,
+                        &selected_input,
+                        input_directory(&selected_input),
+                        &archive_set.output_stem,
+                    )?;
+                    let mut listing = seven_zip.list(archive_input, &password.value)?;
+                    listing.properties.header_encrypted = true;
+                    Ok(listing)
+                }
+                result => result,
+            })
+            .collect()
+    }
+
+    pub fn verify(&self, input: impl AsRef<Path>) -> Result<VerificationReport, ExtractionError> {
+        let input = input.as_ref();
+        self.check_input(input)?;
+        let selected_input = absolute_path(input)?;
+        let archive_set = match resolve_archive_set(&selected_input) {
+            Ok(archive_set) => archive_set,
+            Err(ExtractionError::MissingVolume(volume)) => {
+                return Ok(VerificationReport {
+                    input: selected_input.clone(),
+                    sources: vec![selected_input],
+                    password_sources: Vec::new(),
+                    problems: vec![VerificationProblem::MissingVolume { volume }],
+                });
+            }
+            Err(error) => return Err(error),
+        };
+        let seven_zip = SevenZip::new(&self.seven_zip);
+
+        let mut password_sources = Vec::new();
+        let problems = match detect_input_format(&seven_zip, &archive_set.primary)? {
+            DetectedInputFormat::AppendedArchive { embedded } => {
+                seven_zip
+                    .verify_embedded(&archive_set.primary, &embedded)?
+                    .problems
+            }
+            DetectedInputFormat::RegularArchive => {
+                let archive_input = archive_set.primary.as_path();
+                let mut report = seven_zip.verify(archive_input, "")?;
+                if report.password_rejected {
+                    let password = self.resolve_password(
+                        &seven_zip,
+                        self.password_prompt.as_ref(),
+                        archive_input,
+                        &selected_input,
+                        input_directory(&selected_input),
+                        &archive_set.output_stem,
+                    )?;
+                    report = seven_zip.verify(archive_input, &password.value)?;
+                    password_sources.extend(password.source);
+                }
+                report.problems
+            }
+        };
+
+        Ok(VerificationReport {
+            input: selected_input,
+            sources: archive_set.sources,
+            password_sources,
+            problems,
+        })
+    }
+
+    fn check_input(&self, input: &Path) -> Result<(), ExtractionError> {
+        if !input.exists() {
+            return Err(ExtractionError::InputNotFound(input.to_path_buf()));
+        }
+        if !input.is_file() {
+            return Err(ExtractionError::InputNotFile(input.to_path_buf()));
+        }
+        if !self.seven_zip.is_file() {
+            return Err(ExtractionError::EngineNotFound(self.seven_zip.clone()));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn password_prompt(&self) -> &dyn PasswordPrompt {
+        self.password_prompt.as_ref()
+    }
+
+    pub(crate) fn archive_set_key(&self, input: &Path) -> PathBuf {
+        absolute_path(input)
+            .and_then(|input| resolve_archive_set(&input))
+            .map(|archive_set| archive_set.primary)
+            .unwrap_or_else(|_| input.to_path_buf())
+    }
+
+    pub(crate) fn probe_archive(
+        &self,
+        input: &Path,
+    ) -> Result<Option<Vec<PathBuf>>, ExtractionError> {
+        if !self.seven_zip.is_file() {
+            return Err(ExtractionError::EngineNotFound(self.seven_zip.clone()));
+        }
+
+        let archive_set = resolve_archive_set(&absolute_path(input)?)?;
+        let seven_zip = SevenZip::new(&self.seven_zip);
+        match detect_input_format(&seven_zip, &archive_set.primary) {
+            Ok(_) => {
+                let mut sources = archive_set.sources;
+                sources.retain(|source| source != &archive_set.primary);
+                sources.insert(0, archive_set.primary);
+                Ok(Some(sources))
+            }
+            Err(ExtractionError::UnsupportedInput(_)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn extract_archive(
+        &self,
+        seven_zip: &SevenZip,
+        password_prompt: &dyn PasswordPrompt,
+        set: &ArchiveSet,
+        prompt_input: &Path,
+        destination: &Path,
+        warnings: &mut Vec<ExtractionWarning>,
+    ) -> Result<(ResolvedPassword, Vec<(PathBuf, u32)>), ExtractionError> {
+        let archive_input = set.primary.as_path();
+        let password = self.resolve_password(
+            seven_zip,
+            password_prompt,
+            archive_input,
+            prompt_input,
+            input_directory(archive_input),
+            &set.output_stem,
+        )?;
+        let listing =
+            extract_resolved(seven_zip, archive_input, &password.value, destination, &[])?;
+        let directory_modes = self.restore_metadata(archive_input, &listing, destination, warnings);
+        Ok((password, directory_modes))
+    }
+
+    fn restore_metadata(
+        &self,
+        archive_input: &Path,
+        listing: &ArchiveListing,
+        extracted: &Path,
+        warnings: &mut Vec<ExtractionWarning>,
+    ) -> Vec<(PathBuf, u32)> {
+        if self.extended_attributes && listing.properties.archive_type.as_deref() == Some("tar") {
+            warnings.extend(restore_extended_attributes(archive_input, extracted));
+        }
+        let mut directory_modes = Vec::new();
+        warnings.extend(restore_listed_metadata(
+            listing,
+            extracted,
+            &mut directory_modes,
+        ));
+        for entry in fs::read_dir(extracted).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let listed = listing
+                .entries
+                .iter()
+                .any(|listed| listed.modified.is_some() && path == extracted.join(&listed.path));
+            if !listed
+                && entry.file_type().is_ok_and(|file_type| file_type.is_dir())
+                && let Err(error) = settle_directory_modified(&path)
+            {
+                warnings.push(ExtractionWarning::MetadataNotRestored {
+                    path,
+                    message: error.to_string(),
+                });
+            }
+        }
+        directory_modes
+    }
+
+    fn extract_nested_archives(
+        &self,
+        input: &Path,
+        password_prompt: &dyn PasswordPrompt,
+        extracted: PathBuf,
+        passwords: &mut Vec<ResolvedPassword>,
+        directory_modes: &mut Vec<(PathBuf, u32)>,
+        warnings: &mut Vec<ExtractionWarning>,
+    ) -> Result<PathBuf, ExtractionError> {
+        if self.recursion_depth == 0 {
+            return Ok(extracted);
+        }
+
+        let seven_zip = &SevenZip::new(&self.seven_zip);
+        let mut current = extracted;
+        let mut shown = input.to_path_buf();
+        let mut seen = HashSet::new();
+        for depth in 1..=self.recursion_depth + 1 {
+            remove_platform_metadata(&current)?;
+            let Some(sets) = nested_archive_sets(seven_zip, &current)? else {
+                break;
+            };
+            if depth > self.recursion_depth {
+                warnings.push(ExtractionWarning::RecursiveExtractionStopped {
+                    archive: nested_archive_name(&sets[0].primary),
+                    reason: format!(
+                        "nested archive depth limit of {} reached",
+                        self.recursion_depth
+                    ),
+                });
+                break;
+            }
+            if let Some(repeated) = first_repeated_archive(&sets, &mut seen)? {
+                warnings.push(ExtractionWarning::RecursiveExtractionStopped {
+                    archive: nested_archive_name(&repeated),
+                    reason: "nested archive repeats an archive that was already extracted"
+                        .to_owned(),
+                });
+                break;
+            }
+
+            let mut next = current.clone().into_os_string();
+            next.push(format!("-nested-{depth}"));
+            let next = PathBuf::from(next);
+            if let [set] = sets.as_slice() {
+                shown = shown.join(nested_archive_name(&set.primary));
+                let (password, modes) =
+                    self.extract_archive(seven_zip, password_prompt, set, &shown, &next, warnings)?;
+                passwords.push(password);
+                directory_modes.extend(modes);
+            } else {
+                fs::create_dir(&next).map_err(|error| {
+                    file_system_error("create extraction directory", &next, error)
+                })?;
+                for set in &sets {
+                    let destination = unique_directory_destination(&next, &set.output_stem, &[]);
+                    let (password, modes) = self.extract_archive(
+                        seven_zip,
+                        password_prompt,
+                        set,
+                        &shown.join(nested_archive_name(&set.primary)),
+                        &destination,
+                        warnings,
+                    )?;
+                    passwords.push(password);
+                    directory_modes.extend(modes);
+                }
+            }
+            current = next;
+        }
+        Ok(current)
+    }
+
     fn resolve_password(
         &self,
         seven_zip: &SevenZip,
+        password_prompt: &dyn PasswordPrompt,
         archive_input: &Path,
         prompt_input: &Path,
+        hint_directory: &Path,
+        stem: &OsStr,
     ) -> Result<ResolvedPassword, ExtractionError> {
         match seven_zip.test_password(archive_input, "") {
             Ok(()) => return Ok(ResolvedPassword::empty()),
@@ -240,35 +887,65 @@ impl ExtractionWorkflow {
             Err(error) => return Err(error),
         }
 
-        if let Some(store) = &self.password_store {
-            let candidates =
-                store
-                    .candidates()
-                    .map_err(|message| ExtractionError::PasswordStore {
-                        path: store.path().to_path_buf(),
-                        message,
-                    })?;
-            for password in candidates {
-                match seven_zip.test_password(archive_input, &password) {
-                    Ok(()) => {
-                        return Ok(ResolvedPassword {
-                            value: password,
-                            remember: true,
-                            keep_original: false,
-                        });
-                    }
-                    Err(ExtractionError::WrongPassword) => {}
-                    Err(error) => return Err(error),
+        let fingerprint = self
+            .password_store
+            .as_ref()
+            .and_then(|_| ArchiveFingerprint::read(archive_input, stem).ok());
+        let request = PasswordRequest::new(
+            archive_input,
+            stem,
+            hint_directory,
+            self.password_store.as_ref(),
+            fingerprint.as_ref(),
+            password_prompt,
+        );
+        let mut tried = HashSet::new();
+        let mut failed = Vec::new();
+        let mut listing = None;
+        for provider in &self.password_providers {
+            let mut candidates = match provider.candidates(&request) {
+                Ok(candidates) => candidates,
+                Err(error) => {
+                    warn!("skipped password candidates after an error: {error}");
+                    continue;
                 }
+            };
+            candidates.retain(|candidate| tried.insert(candidate.password.clone()));
+            if candidates.is_empty() {
+                continue;
+            }
+            let entry = empty_password_listing(seven_zip, archive_input, &mut listing)?
+                .and_then(ArchiveListing::smallest_encrypted_entry);
+            let working =
+                first_working_candidate(seven_zip, archive_input, entry.as_deref(), &candidates)?;
+            let mut candidates = candidates.into_iter();
+            failed.extend(
+                candidates
+                    .by_ref()
+                    .take(working.unwrap_or(usize::MAX))
+                    .map(|candidate| candidate.password),
+            );
+            if let Some(candidate) = candidates.next() {
+                return Ok(ResolvedPassword {
+                    value: candidate.password,
+                    remember: candidate.remember,
+                    keep_original: false,
+                    fingerprint,
+                    source: Some(candidate.source),
+                    failed,
+                });
             }
         }
 
+        let comment = empty_password_listing(seven_zip, archive_input, &mut listing)?
+            .and_then(|listing| listing.properties.comment.clone());
         let mut previous_attempt_failed = false;
         loop {
-            let Some(response) = self
-                .password_prompt
-                .request_password(prompt_input, previous_attempt_failed)
-            else {
+            let Some(response) = password_prompt.request_password(
+                prompt_input,
+                previous_attempt_failed,
+                comment.as_deref(),
+            ) else {
                 return Err(ExtractionError::PasswordRequired(
                     prompt_input.to_path_buf(),
                 ));
@@ -280,6 +957,9 @@ impl ExtractionWorkflow {
                         value: response.password,
                         remember: response.remember,
                         keep_original: response.keep_original,
+                        fingerprint,
+                        source: Some("prompt".to_owned()),
+                        failed,
                     });
                 }
                 Err(ExtractionError::WrongPassword) => previous_attempt_failed = true,
@@ -289,9 +969,135 @@ impl ExtractionWorkflow {
     }
 }
 
+const MAX_PASSWORD_TESTS: usize = 4;
+const PASSWORD_TEST_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn extract_resolved(
+    seven_zip: &SevenZip,
+    archive_input: &Path,
+    password: &str,
+    destination: &Path,
+    include: &[String],
+) -> Result<ArchiveListing, ExtractionError> {
+    let listing = seven_zip.list(archive_input, password)?;
+    listing.validate_paths()?;
+
+    fs::create_dir(destination)
+        .map_err(|error| file_system_error("create extraction directory", destination, error))?;
+    seven_zip.extract(archive_input, destination, password, include)?;
+    validate_extracted_output(destination)?;
+    Ok(listing)
+}
+
+fn empty_password_listing<'a>(
+    seven_zip: &SevenZip,
+    archive_input: &Path,
+    cache: &'a mut Option<Option<ArchiveListing>>,
+) -> Result<Option<&'a ArchiveListing>, ExtractionError> {
+    if cache.is_none() {
+        *cache = Some(match seven_zip.list(archive_input, "") {
+            Ok(listing) => Some(listing),
+            Err(ExtractionError::WrongPassword) => None,
+            Err(error) => return Err(error),
+        });
+    }
+    Ok(cache.as_ref().and_then(Option::as_ref))
+}
+
+fn default_password_providers() -> Vec<Box<dyn PasswordCandidateProvider>> {
+    vec![
+        Box::new(StoredPasswords),
+        Box::new(PasswordHints::default()),
+    ]
+}
+
+fn first_working_candidate(
+    seven_zip: &SevenZip,
+    archive_input: &Path,
+    entry: Option<&str>,
+    candidates: &[PasswordCandidate],
+) -> Result<Option<usize>, ExtractionError> {
+    let passwords: Vec<_> = candidates
+        .iter()
+        .map(|candidate| candidate.password.clone())
+        .collect();
+    let mut start = 0;
+    while let Some(index) =
+        first_matching_candidate(seven_zip, archive_input, entry, &passwords[start..])?
+    {
+        match seven_zip.test_password(archive_input, &passwords[start + index]) {
+            Ok(()) => return Ok(Some(start + index)),
+            Err(ExtractionError::WrongPassword) => start += index + 1,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(None)
+}
+
+fn first_matching_candidate(
+    seven_zip: &SevenZip,
+    archive_input: &Path,
+    entry: Option<&str>,
+    candidates: &[String],
+) -> Result<Option<usize>, ExtractionError> {
+    let limit = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(MAX_PASSWORD_TESTS);
+    let mut running: Vec<(usize, PasswordTest)> = Vec::new();
+    let mut next = 0;
+    let mut best = None;
+    loop {
+        while running.len() < limit
+            && next < candidates.len()
+            && best.is_none_or(|best| next < best)
+        {
+            match seven_zip.spawn_password_test(archive_input, &candidates[next], entry) {
+                Ok(test) => running.push((next, test)),
+                Err(error) => {
+                    running.into_iter().for_each(|(_, test)| test.cancel());
+                    return Err(error);
+                }
+            }
+            next += 1;
+        }
+        if running.is_empty() {
+            return Ok(best);
+        }
+
+        let mut still_running = Vec::with_capacity(running.len());
+        let mut failure = None;
+        for (index, mut test) in running {
+            match test.poll() {
+                None => still_running.push((index, test)),
+                Some(Ok(())) => best = Some(best.map_or(index, |best: usize| best.min(index))),
+                Some(Err(ExtractionError::WrongPassword)) => {}
+                Some(Err(error)) => failure = Some(error),
+            }
+        }
+        if let Some(error) = failure {
+            still_running
+                .into_iter()
+                .for_each(|(_, test)| test.cancel());
+            return Err(error);
+        }
+
+        running = Vec::with_capacity(still_running.len());
+        for (index, test) in still_running {
+            if best.is_some_and(|best| index > best) {
+                test.cancel();
+            } else {
+                running.push((index, test));
+            }
+        }
+        if !running.is_empty() {
+            thread::sleep(PASSWORD_TEST_POLL_INTERVAL);
+        }
+    }
+}
+
 enum DetectedInputFormat {
     RegularArchive,
-    Steganographier { embedded: PathBuf },
+    AppendedArchive { embedded: Vec<EmbeddedArchive> },
 }
 
 impl DetectedInputFormat {
@@ -300,25 +1106,173 @@ impl DetectedInputFormat {
         seven_zip: &SevenZip,
         input: &Path,
         prepared: &Path,
-    ) -> Result<PathBuf, ExtractionError> {
+    ) -> Result<Vec<PathBuf>, ExtractionError> {
         match self {
-            Self::RegularArchive => Ok(input.to_path_buf()),
-            Self::Steganographier { embedded } => {
+            Self::RegularArchive => Ok(vec![input.to_path_buf()]),
+            Self::AppendedArchive { embedded } => {
                 fs::create_dir(prepared).map_err(|error| {
                     file_system_error("create special-format workspace", prepared, error)
                 })?;
-                let archive = seven_zip.extract_embedded_archive(input, prepared, &embedded)?;
-                validate_extracted_output(prepared)?;
-                if !archive.is_file() {
-                    return Err(ExtractionError::UnsupportedInput(input.to_path_buf()));
-                }
-                match seven_zip.probe(&archive) {
-                    Ok(()) | Err(ExtractionError::WrongPassword) => Ok(archive),
-                    Err(_) => Err(ExtractionError::UnsupportedInput(input.to_path_buf())),
+                let mut archives = Vec::new();
+                for embedded in &embedded {
+                    let archive =
+                        seven_zip.extract_embedded_archive(input, prepared, &embedded.path)?;
+                    validate_extracted_output(prepared)?;
+                    if !archive.is_file() {
+                        return Err(ExtractionError::UnsupportedInput(input.to_path_buf()));
+                    }
+                    let properties = match seven_zip.list(&archive, "") {
+                        Ok(listing) => listing.properties,
+                        Err(ExtractionError::WrongPassword) => Default::default(),
+                        Err(_) => {
+                            return Err(ExtractionError::UnsupportedInput(input.to_path_buf()));
+                        }
+                    };
+                    archives.push((archive, properties));
                 }
+                group_embedded_volumes(prepared, archives)
+            }
+        }
+    }
+}
+
+fn group_embedded_volumes(
+    prepared: &Path,
+    archives: Vec<(PathBuf, ArchiveProperties)>,
+) -> Result<Vec<PathBuf>, ExtractionError> {
+    let mut sets: Vec<(Vec<PathBuf>, ArchiveProperties)> = Vec::new();
+    for (archive, properties) in archives {
+        match sets.last_mut() {
+            Some((set, previous))
+                if previous.multivolume
+                    && properties.multivolume
+                    && previous.volume_index.map(|index| index + 1) == properties.volume_index =>
+            {
+                set.push(archive);
+                *previous = properties;
             }
+            _ => sets.push((vec![archive], properties)),
+        }
+    }
+
+    let mut inputs = Vec::new();
+    for (index, (set, properties)) in sets.into_iter().enumerate() {
+        if set.len() == 1 {
+            inputs.extend(set);
+            continue;
+        }
+
+        let archive_type = properties
+            .archive_type
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut names = (0..set.len())
+            .map(|sequence| embedded_volume_name(&archive_type, index, sequence, set.len()));
+        let mut entry_volume = None;
+        for volume in &set {
+            let target = prepared.join(names.next().expect("one name per volume"));
+            fs::rename(volume, &target)
+                .map_err(|error| file_system_error("prepare embedded volume", volume, error))?;
+            if entry_volume.is_none() || archive_type == "zip" {
+                entry_volume = Some(target);
+            }
+        }
+        inputs.extend(entry_volume);
+    }
+    Ok(inputs)
+}
+
+fn embedded_volume_name(archive_type: &str, set: usize, sequence: usize, count: usize) -> String {
+    let stem = format!("volume-set-{set}");
+    match archive_type {
+        "zip" if sequence + 1 == count => format!("{stem}.zip"),
+        "zip" => format!("{stem}.z{:02}", sequence + 1),
+        "7z" => format!("{stem}.7z.{:03}", sequence + 1),
+        _ => format!("{stem}.part{}.rar", sequence + 1),
+    }
+}
+
+const NESTED_ARCHIVE_EXTENSIONS: &[&str] = &[
+    "7z", "zip", "rar", "tar", "gz", "tgz", "bz2", "tbz", "tbz2", "xz", "txz", "zst", "tzst", "lz",
+    "lzma", "cab", "arj", "lzh", "cpio",
+];
+
+fn nested_archive_sets(
+    seven_zip: &SevenZip,
+    directory: &Path,
+) -> Result<Option<Vec<ArchiveSet>>, ExtractionError> {
+    let mut entries = fs::read_dir(directory)
+        .map_err(|error| file_system_error("inspect extracted directory", directory, error))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| file_system_error("inspect extracted entry in", directory, error))?;
+    entries.sort();
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let mut sets: Vec<ArchiveSet> = Vec::new();
+    for path in entries {
+        if sets.iter().any(|set| set.sources.contains(&path)) {
+            continue;
+        }
+        let is_file = fs::symlink_metadata(&path)
+            .map_err(|error| file_system_error("inspect extracted entry", &path, error))?
+            .is_file();
+        if !is_file || !has_nested_archive_name(&path) {
+            return Ok(None);
+        }
+
+        let set = resolve_archive_set(&path)?;
+        match seven_zip.probe(&set.primary) {
+            Ok(()) | Err(ExtractionError::WrongPassword) => sets.push(set),
+            Err(ExtractionError::UnsupportedInput(_)) => return Ok(None),
+            Err(error) => return Err(error),
         }
     }
+    Ok(Some(sets))
+}
+
+fn has_nested_archive_name(path: &Path) -> bool {
+    numeric_extension(path).is_some()
+        || zip_volume_sequence(path).is_some()
+        || path
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| {
+                NESTED_ARCHIVE_EXTENSIONS
+                    .iter()
+                    .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+            })
+}
+
+fn first_repeated_archive(
+    sets: &[ArchiveSet],
+    seen: &mut HashSet<[u8; 32]>,
+) -> Result<Option<PathBuf>, ExtractionError> {
+    for set in sets {
+        if !seen.insert(file_fingerprint(&set.primary)?) {
+            return Ok(Some(set.primary.clone()));
+        }
+    }
+    Ok(None)
+}
+
+fn file_fingerprint(path: &Path) -> Result<[u8; 32], ExtractionError> {
+    let mut file = fs::File::open(path)
+        .map_err(|error| file_system_error("fingerprint nested archive", path, error))?;
+    let mut digest = Sha256::new();
+    std::io::copy(&mut file, &mut digest)
+        .map_err(|error| file_system_error("fingerprint nested archive", path, error))?;
+    Ok(digest.finalize().into())
+}
+
+fn input_directory(input: &Path) -> &Path {
+    input.parent().unwrap_or(Path::new(""))
+}
+
+fn nested_archive_name(path: &Path) -> PathBuf {
+    path.file_name().map(PathBuf::from).unwrap_or_default()
 }
 
 trait InputFormatHandler {
@@ -329,27 +1283,38 @@ trait InputFormatHandler {
     ) -> Result<Option<DetectedInputFormat>, ExtractionError>;
 }
 
-struct SteganographierHandler;
+struct AppendedArchiveHandler;
 
-impl InputFormatHandler for SteganographierHandler {
+impl InputFormatHandler for AppendedArchiveHandler {
     fn detect(
         &self,
         seven_zip: &SevenZip,
         input: &Path,
     ) -> Result<Option<DetectedInputFormat>, ExtractionError> {
-        let is_video = input
-            .extension()
-            .and_then(OsStr::to_str)
-            .is_some_and(|extension| {
-                extension.eq_ignore_ascii_case("mp4") || extension.eq_ignore_ascii_case("mkv")
-            });
-        if !is_video {
+        let Some(carrier) = CarrierFormat::detect(input)
+            .map_err(|error| file_system_error("inspect carrier format of", input, error))?
+        else {
             return Ok(None);
-        }
+        };
 
-        seven_zip.embedded_archive(input).map(|embedded| {
-            embedded.map(|embedded| DetectedInputFormat::Steganographier { embedded })
-        })
+        let mut accepted: Vec<EmbeddedArchive> = Vec::new();
+        for embedded in seven_zip.embedded_archives(input)? {
+            if let Some(previous) = accepted.last() {
+                if embedded.offset >= previous.offset.saturating_add(previous.size) {
+                    accepted.push(embedded);
+                }
+                continue;
+            }
+
+            let carrier_end = carrier
+                .end(input, embedded.offset)
+                .map_err(|error| file_system_error("inspect carrier format of", input, error))?;
+            if carrier_end.is_some_and(|end| end <= embedded.offset) {
+                accepted.push(embedded);
+            }
+        }
+        Ok((!accepted.is_empty())
+            .then_some(DetectedInputFormat::AppendedArchive { embedded: accepted }))
     }
 }
 
@@ -375,7 +1340,7 @@ fn detect_input_format(
     seven_zip: &SevenZip,
     input: &Path,
 ) -> Result<DetectedInputFormat, ExtractionError> {
-    let handlers: [&dyn InputFormatHandler; 2] = [&SteganographierHandler, &RegularArchiveHandler];
+    let handlers: [&dyn InputFormatHandler; 2] = [&AppendedArchiveHandler, &RegularArchiveHandler];
     for handler in handlers {
         if let Some(format) = handler.detect(seven_zip, input)? {
             return Ok(format);
@@ -384,10 +1349,11 @@ fn detect_input_format(
     Err(ExtractionError::UnsupportedInput(input.to_path_buf()))
 }
 
-struct ArchiveSet {
-    primary: PathBuf,
-    sources: Vec<PathBuf>,
-    output_stem: OsString,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSet {
+    pub primary: PathBuf,
+    pub sources: Vec<PathBuf>,
+    pub output_stem: OsString,
 }
 
 fn resolve_archive_set(selected: &Path) -> Result<ArchiveSet, ExtractionError> {
@@ -628,6 +1594,9 @@ struct ResolvedPassword {
     value: String,
     remember: bool,
     keep_original: bool,
+    fingerprint: Option<ArchiveFingerprint>,
+    source: Option<String>,
+    failed: Vec<String>,
 }
 
 impl ResolvedPassword {
@@ -636,17 +1605,21 @@ impl ResolvedPassword {
             value: String::new(),
             remember: false,
             keep_original: false,
+            fingerprint: None,
+            source: None,
+            failed: Vec::new(),
         }
     }
 }
 
-struct NoPasswordPrompt;
+pub(crate) struct NoPasswordPrompt;
 
 impl PasswordPrompt for NoPasswordPrompt {
     fn request_password(
         &self,
         _input: &Path,
         _previous_attempt_failed: bool,
+        _comment: Option<&str>,
     ) -> Option<PasswordResponse> {
         None
     }
@@ -695,7 +1668,7 @@ fn validate_extracted_output(root: &Path) -> Result<(), ExtractionError> {
     Ok(())
 }
 
-fn absolute_path(path: &Path) -> Result<PathBuf, ExtractionError> {
+pub(crate) fn absolute_path(path: &Path) -> Result<PathBuf, ExtractionError> {
     if path.is_absolute() {
         return Ok(path.to_path_buf());
     }
@@ -705,11 +1678,17 @@ fn absolute_path(path: &Path) -> Result<PathBuf, ExtractionError> {
         .map_err(|error| file_system_error("resolve absolute path for", path, error))
 }
 
-fn commit_output(
+struct StagedOutput {
+    source: PathBuf,
+    name: OsString,
+    folder: bool,
+}
+
+fn stage_output(
     input: &Path,
     extracted: &Path,
     output_stem: &OsStr,
-) -> Result<PathBuf, ExtractionError> {
+) -> Result<StagedOutput, ExtractionError> {
     remove_platform_metadata(extracted)?;
     let mut entries = fs::read_dir(extracted)
         .map_err(|error| file_system_error("read extracted contents from", extracted, error))?
@@ -720,21 +1699,293 @@ fn commit_output(
         0 => Err(ExtractionError::EmptyArchive(input.to_path_buf())),
         1 => {
             let entry = entries.pop().expect("one extracted entry");
-            let source = entry.path();
-            let parent = input.parent().expect("validated input parent");
-            let target = unique_file_destination(parent, &entry.file_name());
-            fs::rename(&source, &target)
-                .map_err(|error| file_system_error("commit extracted output to", &target, error))?;
-            Ok(target)
+            Ok(StagedOutput {
+                source: entry.path(),
+                name: entry.file_name(),
+                folder: false,
+            })
         }
         _ => {
-            let parent = input.parent().expect("validated input parent");
-            let target = unique_directory_destination(parent, output_stem);
-            fs::rename(extracted, &target)
-                .map_err(|error| file_system_error("commit extracted output to", &target, error))?;
-            Ok(target)
+            settle_directory_modified(extracted)
+                .map_err(|error| file_system_error("set modification time of", extracted, error))?;
+            Ok(StagedOutput {
+                source: extracted.to_path_buf(),
+                name: output_stem.to_owned(),
+                folder: true,
+            })
+        }
+    }
+}
+
+fn commit_staged(input: &Path, staged: &StagedOutput) -> Result<PathBuf, ExtractionError> {
+    let parent = input.parent().expect("validated input parent");
+    let mut taken = Vec::new();
+    loop {
+        let target = if staged.folder {
+            unique_directory_destination(parent, &staged.name, &taken)
+        } else {
+            unique_file_destination(parent, &staged.name, &taken)
+        };
+        match rename_no_replace(&staged.source, &target) {
+            Ok(()) => return Ok(target),
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => taken.push(target),
+            Err(error) => {
+                return Err(file_system_error(
+                    "commit extracted output to",
+                    &target,
+                    error,
+                ));
+            }
+        }
+    }
+}
+
+fn commit_outputs(input: &Path, staged: &[StagedOutput]) -> Result<Vec<PathBuf>, ExtractionError> {
+    let mut committed = Vec::new();
+    for output in staged {
+        match commit_staged(input, output) {
+            Ok(target) => committed.push(target),
+            Err(error) => {
+                if let Err(failures) = roll_back_commits(staged, &committed) {
+                    return Err(ExtractionError::FileSystem {
+                        operation: "roll back committed outputs of",
+                        path: input.to_path_buf(),
+                        message: format!("{failures} (after: {error})"),
+                    });
+                }
+                return Err(error);
+            }
+        }
+    }
+    Ok(committed)
+}
+
+fn roll_back_commits(staged: &[StagedOutput], committed: &[PathBuf]) -> Result<(), String> {
+    let failures: Vec<_> = staged
+        .iter()
+        .zip(committed)
+        .rev()
+        .filter_map(|(output, target)| {
+            fs::rename(target, &output.source)
+                .err()
+                .map(|error| format!("could not restore {}: {error}", target.display()))
+        })
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+#[cfg(test)]
+fn commit_output(
+    input: &Path,
+    extracted: &Path,
+    output_stem: &OsStr,
+) -> Result<PathBuf, ExtractionError> {
+    commit_staged(input, &stage_output(input, extracted, output_stem)?)
+}
+
+fn write_comment_file(extracted: &Path, comment: &str) -> Result<(), ExtractionError> {
+    remove_platform_metadata(extracted)?;
+    let entries = fs::read_dir(extracted)
+        .map_err(|error| file_system_error("read extracted contents from", extracted, error))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| file_system_error("read extracted entry from", extracted, error))?;
+    let (directory, modified) = match entries.as_slice() {
+        [only] if fs::symlink_metadata(only).is_ok_and(|metadata| metadata.is_dir()) => (
+            only.as_path(),
+            fs::metadata(only).and_then(|metadata| metadata.modified()),
+        ),
+        _ => (extracted, newest_child_modified(extracted)),
+    };
+    let modified = modified
+        .map_err(|error| file_system_error("read modification time of", directory, error))?;
+    let path = unique_file_destination(directory, OsStr::new("comment.txt"), &[]);
+    fs::write(&path, comment)
+        .map_err(|error| file_system_error("write archive comment to", &path, error))?;
+    for path in [path.as_path(), directory] {
+        set_modified(path, modified)
+            .map_err(|error| file_system_error("set modification time of", path, error))?;
+    }
+    Ok(())
+}
+
+fn restore_listed_metadata(
+    listing: &ArchiveListing,
+    extracted: &Path,
+    directory_modes: &mut Vec<(PathBuf, u32)>,
+) -> Vec<ExtractionWarning> {
+    let mut warnings = Vec::new();
+    for entry in &listing.entries {
+        let path = extracted.join(&entry.path);
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+        let mut result = Ok(());
+        if let Some(modified) = entry.modified
+            && metadata
+                .modified()
+                .is_ok_and(|applied| !same_modification_time(applied, modified))
+        {
+            result = set_modified(&path, modified);
+        }
+        if let Some(mode) = entry.mode
+            && result.is_ok()
+        {
+            let mode = mode & 0o777;
+            if metadata.is_dir() {
+                directory_modes.push((path.clone(), mode));
+            } else {
+                result = set_mode(&path, &metadata, mode);
+            }
+        }
+        if let Err(error) = result {
+            warnings.push(ExtractionWarning::MetadataNotRestored {
+                path,
+                message: error.to_string(),
+            });
+        }
+    }
+    warnings
+}
+
+fn apply_directory_modes(
+    directory_modes: &[(PathBuf, u32)],
+    staged: &[StagedOutput],
+    outputs: &[PathBuf],
+) -> Vec<ExtractionWarning> {
+    let mut committed = directory_modes
+        .iter()
+        .filter_map(|(path, mode)| {
+            staged
+                .iter()
+                .zip(outputs)
+                .find_map(|(staged, output)| {
+                    Some(output.join(path.strip_prefix(&staged.source).ok()?))
+                })
+                .map(|path| (path, *mode))
+        })
+        .collect::<Vec<_>>();
+    committed.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+
+    let mut warnings = Vec::new();
+    for (path, mode) in committed {
+        if let Err(error) =
+            fs::symlink_metadata(&path).and_then(|metadata| set_mode(&path, &metadata, mode))
+        {
+            warnings.push(ExtractionWarning::MetadataNotRestored {
+                path,
+                message: error.to_string(),
+            });
         }
     }
+    warnings
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, metadata: &fs::Metadata, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.permissions().mode() & 0o7777 == mode {
+        return Ok(());
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _metadata: &fs::Metadata, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn restore_extended_attributes(archive_input: &Path, extracted: &Path) -> Vec<ExtractionWarning> {
+    let attributes = match read_pax_attributes(archive_input) {
+        Ok(attributes) => attributes,
+        Err(error) => {
+            return vec![ExtractionWarning::MetadataNotRestored {
+                path: archive_input.to_path_buf(),
+                message: error.to_string(),
+            }];
+        }
+    };
+    let mut warnings = Vec::new();
+    for entry in &attributes {
+        let Some(relative) = entry.relative_path() else {
+            continue;
+        };
+        let path = extracted.join(relative);
+        if !fs::symlink_metadata(&path).is_ok_and(|metadata| !metadata.file_type().is_symlink()) {
+            continue;
+        }
+        match entry.restore(&path) {
+            Ok(skipped) => {
+                warnings.extend(skipped.into_iter().map(|name| {
+                    ExtractionWarning::MetadataNotRestored {
+                        path: path.clone(),
+                        message: format!("extended attribute {name} is outside the user namespace"),
+                    }
+                }));
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::Unsupported => {
+                warnings.push(ExtractionWarning::MetadataNotRestored {
+                    path: archive_input.to_path_buf(),
+                    message: error.to_string(),
+                });
+                break;
+            }
+            Err(error) => warnings.push(ExtractionWarning::MetadataNotRestored {
+                path,
+                message: error.to_string(),
+            }),
+        }
+    }
+    warnings
+}
+
+fn same_modification_time(applied: SystemTime, listed: SystemTime) -> bool {
+    let difference = applied
+        .duration_since(listed)
+        .or_else(|_| listed.duration_since(applied))
+        .unwrap_or_default();
+    difference < Duration::from_secs(1)
+}
+
+fn newest_child_modified(directory: &Path) -> std::io::Result<SystemTime> {
+    let mut newest = None;
+    for entry in fs::read_dir(directory)? {
+        let modified = entry?.metadata()?.modified()?;
+        newest = newest.max(Some(modified));
+    }
+    match newest {
+        Some(newest) => Ok(newest),
+        None => fs::metadata(directory)?.modified(),
+    }
+}
+
+fn settle_directory_modified(directory: &Path) -> std::io::Result<()> {
+    set_modified(directory, newest_child_modified(directory)?)
+}
+
+fn set_modified(path: &Path, modified: SystemTime) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    options.read(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+
+        const FILE_WRITE_ATTRIBUTES: u32 = 0x0100;
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+        options
+            .access_mode(FILE_WRITE_ATTRIBUTES)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS);
+    }
+    options.open(path)?.set_modified(modified)
 }
 
 fn remove_platform_metadata(extracted: &Path) -> Result<(), ExtractionError> {
@@ -751,24 +2002,67 @@ fn remove_platform_metadata(extracted: &Path) -> Result<(), ExtractionError> {
     Ok(())
 }
 
-fn unique_file_destination(parent: &Path, name: &OsStr) -> PathBuf {
-    let initial = parent.join(name);
-    if !initial.exists() {
-        return initial;
+fn listing_inputs(
+    seven_zip: &SevenZip,
+    primary: &Path,
+) -> Result<(Vec<PathBuf>, Option<tempfile::TempDir>), ExtractionError> {
+    match detect_input_format(seven_zip, primary)? {
+        DetectedInputFormat::RegularArchive => Ok((vec![primary.to_path_buf()], None)),
+        appended @ DetectedInputFormat::AppendedArchive { .. } => {
+            let staging = tempfile::Builder::new()
+                .prefix("ezz-list-")
+                .tempdir()
+                .map_err(|error| file_system_error("create listing staging for", primary, error))?;
+            let inputs = appended.prepare(seven_zip, primary, &staging.path().join("prepared"))?;
+            Ok((inputs, Some(staging)))
+        }
     }
+}
 
-    let name_path = Path::new(name);
-    let stem = name_path.file_stem().unwrap_or(name);
-    let extension = name_path.extension();
-    for sequence in 1_u64.. {
-        let mut candidate = OsString::from(stem);
-        candidate.push(format!(" ({sequence})"));
-        if let Some(extension) = extension {
-            candidate.push(".");
-            candidate.push(extension);
+fn plan_layout(
+    listing: &ArchiveListing,
+    parent: &Path,
+    output_stem: &OsStr,
+    comment_file: bool,
+) -> Option<(PlannedLayout, PathBuf)> {
+    let mut top_level = Vec::new();
+    for entry in &listing.entries {
+        let path = entry.path.to_string_lossy();
+        let name = path.split(['/', '\\']).next().unwrap_or_default();
+        if !name.is_empty()
+            && !matches!(name, "__MACOSX" | ".DS_Store")
+            && !top_level.iter().any(|existing| existing == name)
+        {
+            top_level.push(name.to_owned());
         }
-        let candidate = parent.join(candidate);
-        if !candidate.exists() {
+    }
+
+    let is_directory = |name: &str| {
+        listing.entries.iter().any(|entry| {
+            let path = entry.path.to_string_lossy();
+            (path == name && entry.is_dir)
+                || path
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.starts_with(['/', '\\']))
+        })
+    };
+    match top_level.as_slice() {
+        [] => None,
+        [name] if !comment_file || is_directory(name) => Some((
+            PlannedLayout::SingleItem,
+            unique_file_destination(parent, OsStr::new(name), &[]),
+        )),
+        _ => Some((
+            PlannedLayout::Folder,
+            unique_directory_destination(parent, output_stem, &[]),
+        )),
+    }
+}
+
+fn unique_file_destination(parent: &Path, name: &OsStr, reserved: &[PathBuf]) -> PathBuf {
+    for sequence in 0_u64.. {
+        let candidate = parent.join(numbered_file_name(name, sequence));
+        if !candidate.exists() && !reserved.contains(&candidate) {
             return candidate;
         }
     }
@@ -776,9 +2070,25 @@ fn unique_file_destination(parent: &Path, name: &OsStr) -> PathBuf {
     unreachable!("u64 destination sequence exhausted")
 }
 
-fn unique_directory_destination(parent: &Path, name: &OsStr) -> PathBuf {
+pub(crate) fn numbered_file_name(name: &OsStr, sequence: u64) -> OsString {
+    if sequence == 0 {
+        return name.to_owned();
+    }
+
+    let name_path = Path::new(name);
+    let stem = name_path.file_stem().unwrap_or(name);
+    let mut numbered = OsString::from(stem);
+    numbered.push(format!(" ({sequence})"));
+    if let Some(extension) = name_path.extension() {
+        numbered.push(".");
+        numbered.push(extension);
+    }
+    numbered
+}
+
+fn unique_directory_destination(parent: &Path, name: &OsStr, reserved: &[PathBuf]) -> PathBuf {
     let initial = parent.join(name);
-    if !initial.exists() {
+    if !initial.exists() && !reserved.contains(&initial) {
         return initial;
     }
 
@@ -786,7 +2096,7 @@ fn unique_directory_destination(parent: &Path, name: &OsStr) -> PathBuf {
         let mut candidate = OsString::from(name);
         candidate.push(format!(" ({sequence})"));
         let candidate = parent.join(candidate);
-        if !candidate.exists() {
+        if !candidate.exists() && !reserved.contains(&candidate) {
             return candidate;
         }
     }
@@ -794,27 +2104,70 @@ fn unique_directory_destination(parent: &Path, name: &OsStr) -> PathBuf {
     unreachable!("u64 destination sequence exhausted")
 }
 
-fn file_system_error(
-    operation: &'static str,
-    path: &Path,
-    error: std::io::Error,
-) -> ExtractionError {
-    ExtractionError::FileSystem {
-        operation,
-        path: path.to_path_buf(),
-        message: error.to_string(),
+#[cfg(target_os = "macos")]
+pub(crate) fn rename_no_replace(source: &Path, target: &Path) -> std::io::Result<()> {
+    use std::ffi::{CString, c_char, c_int, c_uint};
+    use std::os::unix::ffi::OsStrExt;
+
+    unsafe extern "C" {
+        fn renamex_np(from: *const c_char, to: *const c_char, flags: c_uint) -> c_int;
+    }
+
+    const RENAME_EXCL: c_uint = 0x0000_0004;
+    let source = CString::new(source.as_os_str().as_bytes())?;
+    let target = CString::new(target.as_os_str().as_bytes())?;
+    if unsafe { renamex_np(source.as_ptr(), target.as_ptr(), RENAME_EXCL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
     }
 }
 
-trait SourceCleaner {
-    fn clean(&self, sources: &[PathBuf]) -> Result<(), String>;
+#[cfg(windows)]
+pub(crate) fn rename_no_replace(source: &Path, target: &Path) -> std::io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn MoveFileExW(existing: *const u16, new: *const u16, flags: u32) -> i32;
+    }
+
+    let wide = |path: &Path| {
+        path.as_os_str()
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<_>>()
+    };
+    let (source, target) = (wide(source), wide(target));
+    if unsafe { MoveFileExW(source.as_ptr(), target.as_ptr(), 0) } != 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
 }
 
-struct TrashCleaner;
+#[cfg(not(any(target_os = "macos", windows)))]
+pub(crate) fn rename_no_replace(source: &Path, target: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(source)?.is_dir() {
+        fs::create_dir(target)?;
+        fs::rename(source, target).inspect_err(|_| {
+            let _ = fs::remove_dir(target);
+        })
+    } else {
+        fs::hard_link(source, target)?;
+        fs::remove_file(source)
+    }
+}
 
-impl SourceCleaner for TrashCleaner {
-    fn clean(&self, sources: &[PathBuf]) -> Result<(), String> {
-        trash::delete_all(sources).map_err(|error| error.to_string())
+fn file_system_error(
+    operation: &'static str,
+    path: &Path,
+    error: std::io::Error,
+) -> ExtractionError {
+    ExtractionError::FileSystem {
+        operation,
+        path: path.to_path_buf(),
+        message: error.to_string(),
     }
 }
 
@@ -823,7 +2176,7 @@ mod tests {
     use std::collections::VecDeque;
     use std::io::Write;
     use std::process::Command;
-    use std::sync::Mutex;
+    use std::sync::{Arc, Mutex};
 
     use super::*;
 
@@ -848,12 +2201,14 @@ mod tests {
 
     struct ScriptedPasswordPrompt {
         responses: Mutex<VecDeque<PasswordResponse>>,
+        comments: Arc<Mutex<Vec<Option<String>>>>,
     }
 
     impl ScriptedPasswordPrompt {
         fn new(responses: impl IntoIterator<Item = PasswordResponse>) -> Self {
             Self {
                 responses: Mutex::new(responses.into_iter().collect()),
+                comments: Arc::default(),
             }
         }
     }
@@ -863,7 +2218,12 @@ mod tests {
             &self,
             _input: &Path,
             _previous_attempt_failed: bool,
+            comment: Option<&str>,
         ) -> Option<PasswordResponse> {
+            self.comments
+                .lock()
+                .unwrap()
+                .push(comment.map(str::to_owned));
             self.responses.lock().unwrap().pop_front()
         }
     }
@@ -875,6 +2235,7 @@ mod tests {
             &self,
             _input: &Path,
             _previous_attempt_failed: bool,
+            _comment: Option<&str>,
         ) -> Option<PasswordResponse> {
             None
         }
@@ -906,6 +2267,9 @@ mod tests {
             ExtractionOutcome {
                 input: archive.clone(),
                 output: payload.clone(),
+                additional_outputs: Vec::new(),
+                password_sources: Vec::new(),
+                comments: Vec::new(),
                 warnings: Vec::new(),
             }
         );
@@ -1338,17 +2702,785 @@ mod tests {
             },
         ]);
 
-        ExtractionWorkflow::with_adapters(&seven_zip, RemoveSource, prompt)
+        ExtractionWorkflow::with_adapters(&seven_zip, RemoveSource, prompt)
+            .extract(&archive)
+            .expect("retry with the correct password");
+
+        assert_eq!(std::fs::read(&payload).unwrap(), b"classified");
+        assert!(!archive.exists(), "successful retry must clean the source");
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn cancelling_the_password_prompt_preserves_the_archive() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("cancelled-secret.txt");
+        let archive = sandbox.path().join("cancelled.7z");
+        std::fs::write(&payload, b"cancelled secret").expect("create secret payload");
+        create_encrypted_archive(
+            &seven_zip,
+            sandbox.path(),
+            &archive,
+            "cancelled-secret.txt",
+            "not entered",
+        );
+        std::fs::remove_file(&payload).expect("remove source payload");
+
+        let result = ExtractionWorkflow::with_adapters(&seven_zip, RemoveSource, NoResponsePrompt)
+            .extract(&archive);
+
+        assert_eq!(
+            result,
+            Err(ExtractionError::PasswordRequired(archive.clone()))
+        );
+        assert!(archive.is_file(), "cancelled archive must be preserved");
+        assert!(
+            !payload.exists(),
+            "cancelled archive must not commit output"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrent_candidates_prefer_the_best_ranked_match() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$2\" in\n\
+             -pslow) sleep 0.3; exit 0 ;;\n\
+             -pfast) exit 0 ;;\n\
+             esac\n\
+             echo 'Wrong password' >&2\n\
+             exit 2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let candidates: Vec<_> = ["wrong", "slow", "fast", "also wrong"]
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        let seven_zip = SevenZip::new(&engine);
+        let archive = sandbox.path().join("secret.7z");
+
+        assert_eq!(
+            first_matching_candidate(&seven_zip, &archive, None, &candidates).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            first_matching_candidate(&seven_zip, &archive, Some("a.txt"), &candidates[2..])
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            first_matching_candidate(&seven_zip, &archive, None, &candidates[3..]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn password_hints_are_tried_before_the_prompt() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$1$2\" in\n\
+             l*|t-psidecar) exit 0 ;;\n\
+             esac\n\
+             echo 'Wrong password' >&2\n\
+             exit 2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("release[pwd=named].7z");
+        let password_store = sandbox.path().join("passwords.json");
+        std::fs::write(&archive, b"7z").unwrap();
+        std::fs::write(sandbox.path().join("password.txt"), "wrong\nsidecar\n").unwrap();
+        let workflow = ExtractionWorkflow::with_adapters_and_password_store(
+            &engine,
+            RemoveSource,
+            ScriptedPasswordPrompt::new([]),
+            &password_store,
+        );
+
+        let password = workflow
+            .resolve_password(
+                &SevenZip::new(&engine),
+                workflow.password_prompt(),
+                &archive,
+                &archive,
+                sandbox.path(),
+                OsStr::new("release[pwd=named]"),
+            )
+            .expect("resolve hinted password");
+
+        assert_eq!(password.value, "sidecar");
+        assert_eq!(password.source.as_deref(), Some("hint"));
+        assert_eq!(password.failed, ["named", "wrong"]);
+        assert!(password.remember);
+        assert!(
+            !password_store.exists(),
+            "hinted passwords must not be stored before extraction succeeds"
+        );
+        assert!(matches!(
+            workflow
+                .with_password_providers(vec![Box::new(StoredPasswords)])
+                .resolve_password(
+                    &SevenZip::new(&engine),
+                    &NoPasswordPrompt,
+                    &archive,
+                    &archive,
+                    sandbox.path(),
+                    OsStr::new("release[pwd=named]"),
+                ),
+            Err(ExtractionError::PasswordRequired(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn failing_password_providers_still_reach_the_prompt() {
+        use std::os::unix::fs::PermissionsExt;
+
+        struct FailingProvider;
+
+        impl PasswordCandidateProvider for FailingProvider {
+            fn candidates(
+                &self,
+                _request: &PasswordRequest<'_>,
+            ) -> Result<Vec<PasswordCandidate>, ExtractionError> {
+                Err(ExtractionError::PasswordProvider {
+                    provider: "command".to_owned(),
+                    message: "exited with exit status: 1".to_owned(),
+                })
+            }
+        }
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$1$2\" in\n\
+             l*|t-pprompted) exit 0 ;;\n\
+             esac\n\
+             echo 'Wrong password' >&2\n\
+             exit 2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("release.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+        let workflow = ExtractionWorkflow::with_adapters(
+            &engine,
+            RemoveSource,
+            ScriptedPasswordPrompt::new([PasswordResponse {
+                password: "prompted".to_owned(),
+                remember: false,
+                keep_original: false,
+            }]),
+        )
+        .with_password_providers(vec![Box::new(FailingProvider)]);
+
+        let password = workflow
+            .resolve_password(
+                &SevenZip::new(&engine),
+                workflow.password_prompt(),
+                &archive,
+                &archive,
+                sandbox.path(),
+                OsStr::new("release"),
+            )
+            .expect("reach the prompt after the failing provider");
+
+        assert_eq!(password.value, "prompted");
+        assert_eq!(password.source.as_deref(), Some("prompt"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn plan_reports_layout_and_destination_without_touching_the_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             printf 'Path = bundle.7z\\nType = 7z\\n\\n----------\\n'\n\
+             printf 'Path = docs\\nFolder = +\\n\\n'\n\
+             printf 'Path = docs/readme.txt\\nEncrypted = +\\n\\n'\n\
+             printf 'Path = __MACOSX/._notes.txt\\n\\n'\n\
+             printf 'Path = notes.txt\\nEncrypted = -\\n'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("bundle.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+        std::fs::create_dir(sandbox.path().join("bundle")).unwrap();
+        let before = directory_names(sandbox.path());
+
+        let plan = ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource)
+            .plan(&archive)
+            .expect("plan extraction");
+
+        assert_eq!(plan.format, InputFormat::Archive);
+        assert_eq!(plan.archive_set.primary, archive);
+        assert_eq!(plan.sources_to_clean, std::slice::from_ref(&archive));
+        let [output] = plan.outputs.as_slice() else {
+            panic!("expected one planned output: {:?}", plan.outputs);
+        };
+        let listing = output.listing.as_ref().expect("listed archive");
+        assert!(output.password_required);
+        assert_eq!(listing.properties.archive_type.as_deref(), Some("7z"));
+        assert_eq!(
+            listing
+                .entries
+                .iter()
+                .map(|entry| entry.path.clone())
+                .collect::<Vec<_>>(),
+            [
+                "docs",
+                "docs/readme.txt",
+                "__MACOSX/._notes.txt",
+                "notes.txt"
+            ]
+            .map(PathBuf::from)
+        );
+        assert!(listing.entries[0].is_dir);
+        assert_eq!(output.layout, Some(PlannedLayout::Folder));
+        assert_eq!(
+            output.destination.as_deref(),
+            Some(sandbox.path().join("bundle (1)").as_path())
+        );
+        assert_eq!(directory_names(sandbox.path()), before);
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn plan_matches_the_committed_single_item_output() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("payload.txt");
+        let archive = sandbox.path().join("archive.7z");
+        std::fs::write(&payload, b"new content").expect("create payload");
+        create_archive(&seven_zip, sandbox.path(), &archive, &["payload.txt"]);
+        let before = directory_names(sandbox.path());
+        let workflow = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource);
+
+        let plan = workflow.plan(&archive).expect("plan extraction");
+
+        assert_eq!(directory_names(sandbox.path()), before);
+        assert_eq!(plan.outputs.len(), 1);
+        assert!(!plan.outputs[0].password_required);
+        let listing = plan.outputs[0].listing.as_ref().expect("listed archive");
+        assert_eq!(listing.entries.len(), 1);
+        assert_eq!(listing.entries[0].path, PathBuf::from("payload.txt"));
+        assert_eq!(listing.entries[0].size, Some(11));
+        assert_eq!(plan.outputs[0].layout, Some(PlannedLayout::SingleItem));
+        let outcome = workflow.extract(&archive).expect("extract planned archive");
+        assert_eq!(plan.outputs[0].destination.as_ref(), Some(&outcome.output));
+        assert_eq!(outcome.output, sandbox.path().join("payload (1).txt"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn selected_entries_are_passed_as_include_switches_and_keep_the_source() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        let switches = sandbox.path().join("switches.log");
+        std::fs::write(
+            &engine,
+            format!(
+                "#!/bin/sh\n\
+                 [ \"$1\" = x ] || exit 0\n\
+                 for argument; do\n\
+                 case \"$argument\" in\n\
+                 -o*) output=\"${{argument#-o}}\" ;;\n\
+                 -i*) echo \"$argument\" >> '{}' ;;\n\
+                 esac\n\
+                 done\n\
+                 echo selected > \"$output/first.txt\"\n",
+                switches.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("bundle.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+        let workflow = ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource);
+
+        let outcome = workflow
+            .extract_selected(
+                &archive,
+                &EntrySelection {
+                    entries: vec!["first.txt".to_owned(), "docs/*.md".to_owned()],
+                    ..Default::default()
+                },
+            )
+            .expect("extract selected entries");
+
+        assert_eq!(outcome.output, sandbox.path().join("first.txt"));
+        assert_eq!(
+            std::fs::read_to_string(&switches).unwrap(),
+            "-i!first.txt\n-i!docs/*.md\n"
+        );
+        assert!(archive.exists(), "partial extraction must keep the source");
+
+        workflow
+            .extract_selected(
+                &archive,
+                &EntrySelection {
+                    entries: vec!["first.txt".to_owned()],
+                    clean_source: true,
+                },
+            )
+            .expect("extract selected entries again");
+        assert!(
+            !archive.exists(),
+            "requested cleanup must remove the source"
+        );
+    }
+
+    #[test]
+    fn empty_selection_is_rejected() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        let archive = sandbox.path().join("bundle.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+
+        assert_eq!(
+            ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource).extract_selected(
+                &archive,
+                &EntrySelection {
+                    entries: Vec::new(),
+                    clean_source: true,
+                },
+            ),
+            Err(ExtractionError::EmptySelection(archive.clone()))
+        );
+        assert!(archive.is_file());
+        assert_eq!(directory_names(sandbox.path()), ["bundle.7z"]);
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn selected_directory_is_committed_as_a_single_item() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let docs = sandbox.path().join("docs");
+        std::fs::create_dir(&docs).expect("create docs payload");
+        std::fs::write(docs.join("readme.md"), b"readme").expect("create readme payload");
+        std::fs::write(docs.join("notes.txt"), b"notes").expect("create notes payload");
+        std::fs::write(sandbox.path().join("large.bin"), b"large").expect("create large payload");
+        let archive = sandbox.path().join("bundle.7z");
+        create_archive(&seven_zip, sandbox.path(), &archive, &["docs", "large.bin"]);
+        std::fs::remove_dir_all(&docs).expect("remove docs payload");
+        std::fs::remove_file(sandbox.path().join("large.bin")).expect("remove large payload");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
+            .extract_selected(
+                &archive,
+                &EntrySelection {
+                    entries: vec!["docs/*.md".to_owned()],
+                    ..Default::default()
+                },
+            )
+            .expect("extract selected entries");
+
+        assert_eq!(outcome.output, docs);
+        assert_eq!(std::fs::read(docs.join("readme.md")).unwrap(), b"readme");
+        assert!(!docs.join("notes.txt").exists());
+        assert!(!sandbox.path().join("large.bin").exists());
+        assert!(archive.exists(), "partial extraction must keep the source");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn verification_resolves_the_password_and_reports_entry_errors() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$1$2$3\" in\n\
+             l*|t-psecret-bso0) exit 0 ;;\n\
+             t-psecret-bsp0) echo 'ERROR: CRC Failed : secret.txt'; exit 2 ;;\n\
+             esac\n\
+             echo 'ERROR: Data Error in encrypted file. Wrong password? : secret.txt' >&2\n\
+             exit 2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("secret.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+        let before = directory_names(sandbox.path());
+        let workflow = ExtractionWorkflow::with_adapters(
+            &engine,
+            RemoveSource,
+            ScriptedPasswordPrompt::new([PasswordResponse {
+                password: "secret".to_owned(),
+                remember: true,
+                keep_original: false,
+            }]),
+        );
+
+        let report = workflow.verify(&archive).expect("verify archive");
+
+        assert!(!report.is_ok());
+        assert_eq!(report.sources, std::slice::from_ref(&archive));
+        assert_eq!(report.password_sources, ["prompt"]);
+        assert_eq!(
+            report.problems,
+            [VerificationProblem::CrcError {
+                entry: PathBuf::from("secret.txt")
+            }]
+        );
+        assert_eq!(directory_names(sandbox.path()), before);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn plan_reports_appended_payloads_without_extracting_them() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        let image = std::fs::read(asset("whatever.jpg")).expect("read JPEG asset");
+        std::fs::write(
+            &engine,
+            format!(
+                "#!/bin/sh\n\
+                 case \"$1$2\" in\n\
+                 l-t#) printf 'Path = 1.zip\\nType = zip\\nOffset = {}\\nSize = 4\\n' ;;\n\
+                 *) echo \"$@\" > \"$(dirname \"$0\")/unexpected\"; exit 2 ;;\n\
+                 esac\n",
+                image.len()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let carrier = sandbox.path().join("carrier.jpg");
+        let mut contents = image;
+        contents.extend(b"PK\x03\x04");
+        std::fs::write(&carrier, contents).unwrap();
+        let before = directory_names(sandbox.path());
+
+        let plan = ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource)
+            .plan(&carrier)
+            .expect("plan carrier");
+
+        assert_eq!(plan.format, InputFormat::AppendedArchive { payloads: 1 });
+        assert_eq!(
+            plan.outputs,
+            [PlannedOutput {
+                password_required: true,
+                listing: None,
+                layout: None,
+                destination: None,
+            }]
+        );
+        assert_eq!(directory_names(sandbox.path()), before);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn verification_reports_missing_volumes_and_tests_carriers_in_place() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        let image = std::fs::read(asset("whatever.jpg")).expect("read JPEG asset");
+        std::fs::write(
+            &engine,
+            format!(
+                "#!/bin/sh\n\
+                 case \"$1$2\" in\n\
+                 l-t#) printf 'Path = 1.zip\\nType = zip\\nOffset = {}\\nSize = 4\\n' ;;\n\
+                 t-t#) echo \"$@\" > \"$(dirname \"$0\")/tested\"\n\
+                 echo 'ERROR: CRC Failed : 1.zip'; exit 2 ;;\n\
+                 *) exit 2 ;;\n\
+                 esac\n",
+                image.len()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let first = sandbox.path().join("release.7z.001");
+        std::fs::write(&first, b"7z").unwrap();
+        std::fs::write(sandbox.path().join("release.7z.003"), b"7z").unwrap();
+        let carrier = sandbox.path().join("carrier.jpg");
+        let mut contents = image;
+        contents.extend(b"PK\x03\x04");
+        std::fs::write(&carrier, contents).unwrap();
+        let workflow = ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource);
+
+        let report = workflow.verify(&first).expect("verify incomplete set");
+        assert_eq!(
+            report.problems,
+            [VerificationProblem::MissingVolume {
+                volume: sandbox.path().join("release.7z.002")
+            }]
+        );
+
+        let report = workflow.verify(&carrier).expect("verify carrier");
+        assert_eq!(
+            report.problems,
+            [VerificationProblem::CrcError {
+                entry: PathBuf::from("1.zip")
+            }]
+        );
+        assert_eq!(
+            std::fs::read_to_string(sandbox.path().join("tested")).unwrap(),
+            format!(
+                "t -t# -p -bsp0 -sccUTF-8 -scsUTF-8 {} 1.zip\n",
+                carrier.display()
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn archive_comment_reaches_the_prompt_the_outcome_and_a_comment_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$1$2$3\" in\n\
+             l*) cat <<'EOF'\n\
+             --\n\
+             Path = notes.7z\n\
+             Type = 7z\n\
+             Comment = {\n\
+             password: secret\n\
+             }\n\
+             \n\
+             ----------\n\
+             Path = notes.txt\n\
+             Size = 5\n\
+             Encrypted = +\n\
+             EOF\n\
+             exit 0 ;;\n\
+             t-psecret-bso0) exit 0 ;;\n\
+             x*) for argument; do\n\
+             case \"$argument\" in -o*) output=\"${argument#-o}\" ;; esac\n\
+             done\n\
+             echo notes > \"$output/notes.txt\"\n\
+             exit 0 ;;\n\
+             esac\n\
+             echo 'ERROR: Data Error in encrypted file. Wrong password? : notes.txt' >&2\n\
+             exit 2\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("notes.7z");
+        std::fs::write(&archive, b"7z").unwrap();
+        let prompt = ScriptedPasswordPrompt::new([PasswordResponse {
+            password: "secret".to_owned(),
+            remember: false,
+            keep_original: true,
+        }]);
+        let comments = Arc::clone(&prompt.comments);
+        let workflow = ExtractionWorkflow::with_adapters(&engine, RemoveSource, prompt)
+            .with_comment_files(true);
+
+        let plan = workflow.plan(&archive).expect("plan commented archive");
+        let outcome = workflow
+            .extract(&archive)
+            .expect("extract commented archive");
+
+        assert_eq!(
+            *comments.lock().unwrap(),
+            [Some("password: secret".to_owned())]
+        );
+        assert_eq!(outcome.comments, ["password: secret"]);
+        assert_eq!(outcome.output, sandbox.path().join("notes"));
+        assert_eq!(plan.outputs[0].layout, Some(PlannedLayout::Folder));
+        assert_eq!(plan.outputs[0].destination.as_ref(), Some(&outcome.output));
+        assert_eq!(
+            std::fs::read_to_string(outcome.output.join("comment.txt")).unwrap(),
+            "password: secret"
+        );
+        assert_eq!(
+            std::fs::read_to_string(outcome.output.join("notes.txt")).unwrap(),
+            "notes\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listed_modes_and_times_are_restored_before_commit() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::UNIX_EPOCH;
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let engine = sandbox.path().join("7zz");
+        std::fs::write(
+            &engine,
+            "#!/bin/sh\n\
+             case \"$1\" in\n\
+             l) cat <<'EOF'\n\
+             --\n\
+             Path = scripts.tar\n\
+             Type = tar\n\
+             \n\
+             ----------\n\
+             Path = scripts/run.sh\n\
+             Size = 10\n\
+             Modified = 2020-01-01 00:00:00\n\
+             Mode = -rwxr-xr-x\n\
+             \n\
+             Path = notes.txt\n\
+             Size = 6\n\
+             Modified = 2021-06-01 12:00:00\n\
+             Mode = -rw-r-----\n\
+             \n\
+             Path = locked\n\
+             Folder = +\n\
+             Modified = 2019-01-01 00:00:00\n\
+             Mode = dr-xr-xr-x\n\
+             \n\
+             Path = locked/su\n\
+             Size = 3\n\
+             Modified = 2019-01-01 00:00:00\n\
+             Mode = -rwsr-xr-x\n\
+             EOF\n\
+             exit 0 ;;\n\
+             x) for argument; do\n\
+             case \"$argument\" in -o*) output=\"${argument#-o}\" ;; esac\n\
+             done\n\
+             mkdir \"$output/scripts\"\n\
+             echo 'echo run' > \"$output/scripts/run.sh\"\n\
+             echo notes > \"$output/notes.txt\"\n\
+             mkdir \"$output/locked\"\n\
+             echo su > \"$output/locked/su\"\n\
+             chmod 644 \"$output/scripts/run.sh\" \"$output/notes.txt\" \"$output/locked/su\" ;;\n\
+             esac\n\
+             exit 0\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archive = sandbox.path().join("scripts.tar");
+        std::fs::write(&archive, b"tar").unwrap();
+        let workflow = ExtractionWorkflow::with_source_cleaner(&engine, RemoveSource)
+            .with_extended_attributes(true);
+
+        let outcome = workflow.extract(&archive).expect("extract tarball");
+
+        assert!(outcome.warnings.is_empty(), "{:?}", outcome.warnings);
+        assert_eq!(outcome.output, sandbox.path().join("scripts"));
+        let modified = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        let script = outcome.output.join("scripts/run.sh");
+        let notes = outcome.output.join("notes.txt");
+        assert_eq!(mode(&script), 0o755);
+        assert_eq!(mode(&notes), 0o640);
+        let locked = outcome.output.join("locked");
+        assert_eq!(mode(&locked.join("su")), 0o755, "setuid is never restored");
+        assert_eq!(mode(&locked), 0o555, "directory modes apply after commit");
+        assert_eq!(
+            modified(&script),
+            UNIX_EPOCH + Duration::from_secs(1_577_836_800)
+        );
+        assert_eq!(
+            modified(&notes),
+            UNIX_EPOCH + Duration::from_secs(1_622_548_800)
+        );
+        assert_eq!(
+            modified(&outcome.output.join("scripts")),
+            modified(&script),
+            "an unlisted directory takes its newest child's time"
+        );
+        assert_eq!(
+            modified(&outcome.output),
+            modified(&notes),
+            "the committed folder takes its newest child's time"
+        );
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn tarball_keeps_exec_bits_and_times() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::UNIX_EPOCH;
+
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let tools = sandbox.path().join("tools");
+        std::fs::create_dir(&tools).expect("create tools payload");
+        let script = tools.join("build.sh");
+        std::fs::write(&script, b"#!/bin/sh\n").expect("create script payload");
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let archived_time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        set_modified(&script, archived_time).expect("age script payload");
+        set_modified(&tools, archived_time).expect("age tools payload");
+        let archive = sandbox.path().join("tools.tar");
+        let status = Command::new(&seven_zip)
+            .current_dir(sandbox.path())
+            .args(["a", "-ttar"])
+            .arg(&archive)
+            .arg("tools")
+            .args(["-bso0", "-bsp0"])
+            .status()
+            .expect("create tarball with 7-Zip");
+        assert!(status.success(), "7-Zip must create the test tarball");
+        std::fs::remove_dir_all(&tools).expect("remove tools payload");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
             .extract(&archive)
-            .expect("retry with the correct password");
+            .expect("extract tarball");
 
-        assert_eq!(std::fs::read(&payload).unwrap(), b"classified");
-        assert!(!archive.exists(), "successful retry must clean the source");
+        assert!(outcome.warnings.is_empty(), "{:?}", outcome.warnings);
+        assert_eq!(outcome.output, tools);
+        let metadata = std::fs::metadata(&script).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+        assert_eq!(metadata.modified().unwrap(), archived_time);
+        assert_eq!(
+            std::fs::metadata(&tools).unwrap().modified().unwrap(),
+            archived_time
+        );
     }
 
     #[test]
     #[ignore = "requires cargo xtask prepare"]
-    fn cancelling_the_password_prompt_preserves_the_archive() {
+    fn verification_reports_intact_and_truncated_archives() {
         let seven_zip = prepared_seven_zip();
         assert!(
             seven_zip.is_file(),
@@ -1356,30 +3488,34 @@ mod tests {
         );
 
         let sandbox = tempfile::tempdir().expect("create test sandbox");
-        let payload = sandbox.path().join("cancelled-secret.txt");
-        let archive = sandbox.path().join("cancelled.7z");
-        std::fs::write(&payload, b"cancelled secret").expect("create secret payload");
-        create_encrypted_archive(
-            &seven_zip,
-            sandbox.path(),
-            &archive,
-            "cancelled-secret.txt",
-            "not entered",
-        );
+        let payload = sandbox.path().join("payload.bin");
+        let archive = sandbox.path().join("archive.7z");
+        std::fs::write(&payload, vec![b'a'; 64 * 1024]).expect("create payload");
+        create_archive(&seven_zip, sandbox.path(), &archive, &["payload.bin"]);
         std::fs::remove_file(&payload).expect("remove source payload");
+        let workflow = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource);
 
-        let result = ExtractionWorkflow::with_adapters(&seven_zip, RemoveSource, NoResponsePrompt)
-            .extract(&archive);
+        let report = workflow.verify(&archive).expect("verify intact archive");
+        assert!(report.is_ok(), "{:?}", report.problems);
+        assert!(report.password_sources.is_empty());
+
+        let archive_file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&archive)
+            .expect("open archive for truncation");
+        let length = archive_file.metadata().unwrap().len();
+        archive_file
+            .set_len(length / 2)
+            .expect("truncate test archive");
+        let before = directory_names(sandbox.path());
+
+        let damaged = workflow.verify(&archive);
 
-        assert_eq!(
-            result,
-            Err(ExtractionError::PasswordRequired(archive.clone()))
-        );
-        assert!(archive.is_file(), "cancelled archive must be preserved");
         assert!(
-            !payload.exists(),
-            "cancelled archive must not commit output"
+            damaged.as_ref().map_or(true, |report| !report.is_ok()),
+            "truncated archive must not verify: {damaged:?}"
         );
+        assert_eq!(directory_names(sandbox.path()), before);
     }
 
     #[test]
@@ -1617,6 +3753,115 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn recursive_mode_extracts_an_archive_only_payload() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("nested-payload.txt");
+        let inner = sandbox.path().join("inner.zip");
+        let outer = sandbox.path().join("outer.7z");
+        std::fs::write(&payload, b"nested payload").expect("create nested payload");
+        create_zip_archive(&seven_zip, sandbox.path(), &inner, "nested-payload.txt");
+        create_archive(&seven_zip, sandbox.path(), &outer, &["inner.zip"]);
+        std::fs::remove_file(&payload).expect("remove source payload");
+        std::fs::remove_file(&inner).expect("remove standalone inner archive");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
+            .with_recursive_extraction(2)
+            .extract(&outer)
+            .expect("extract nested archive");
+
+        assert_eq!(outcome.output, payload);
+        assert!(outcome.warnings.is_empty());
+        assert_eq!(std::fs::read(&payload).unwrap(), b"nested payload");
+        assert!(!inner.exists(), "inner archive must not be committed");
+        assert!(
+            !outer.exists(),
+            "successful extraction must clean the source"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn nested_password_prompt_names_the_outer_archive() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("secret.txt");
+        let inner = sandbox.path().join("inner.7z");
+        let outer = sandbox.path().join("outer.7z");
+        std::fs::write(&payload, b"secret").expect("create nested payload");
+        create_encrypted_archive(&seven_zip, sandbox.path(), &inner, "secret.txt", "inner");
+        create_archive(&seven_zip, sandbox.path(), &outer, &["inner.7z"]);
+        std::fs::remove_file(&payload).expect("remove source payload");
+        std::fs::remove_file(&inner).expect("remove standalone inner archive");
+
+        let result = ExtractionWorkflow::builder(&seven_zip)
+            .source_cleaner(RemoveSource)
+            .password_prompt(NoResponsePrompt)
+            .recursive_extraction(1)
+            .build()
+            .extract(&outer);
+
+        assert_eq!(
+            result,
+            Err(ExtractionError::PasswordRequired(outer.join("inner.7z")))
+        );
+        assert!(outer.is_file(), "failed extraction must keep the source");
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn recursive_mode_stops_at_the_depth_limit_with_a_warning() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("deep-payload.txt");
+        let innermost = sandbox.path().join("innermost.zip");
+        let middle = sandbox.path().join("middle.7z");
+        let outer = sandbox.path().join("outer.7z");
+        std::fs::write(&payload, b"deep payload").expect("create deep payload");
+        create_zip_archive(&seven_zip, sandbox.path(), &innermost, "deep-payload.txt");
+        create_archive(&seven_zip, sandbox.path(), &middle, &["innermost.zip"]);
+        create_archive(&seven_zip, sandbox.path(), &outer, &["middle.7z"]);
+        std::fs::remove_file(&payload).expect("remove source payload");
+        std::fs::remove_file(&innermost).expect("remove standalone innermost archive");
+        std::fs::remove_file(&middle).expect("remove standalone middle archive");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
+            .with_recursive_extraction(1)
+            .extract(&outer)
+            .expect("extract up to the depth limit");
+
+        assert_eq!(outcome.output, innermost);
+        assert!(
+            matches!(
+                outcome.warnings.as_slice(),
+                [ExtractionWarning::RecursiveExtractionStopped { .. }]
+            ),
+            "unexpected warnings: {:?}",
+            outcome.warnings
+        );
+        assert!(
+            !payload.exists(),
+            "archive beyond the limit must stay packed"
+        );
+    }
+
     #[test]
     #[ignore = "requires cargo xtask prepare"]
     fn tar_gzip_and_xz_archives_extract_through_the_shared_workflow() {
@@ -1693,6 +3938,121 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn jpeg_carrier_extracts_its_appended_zip() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let payload = sandbox.path().join("jpeg-hidden.txt");
+        let embedded = sandbox.path().join("jpeg-embedded.zip");
+        let image = sandbox.path().join("carrier.jpg");
+        std::fs::write(&payload, b"JPEG hidden payload").expect("create hidden payload");
+        create_zip_archive(&seven_zip, sandbox.path(), &embedded, "jpeg-hidden.txt");
+        std::fs::remove_file(&payload).expect("remove source payload");
+
+        let mut carrier = std::fs::read(asset("whatever.jpg")).expect("read JPEG asset");
+        carrier.extend(std::fs::read(&embedded).expect("read embedded ZIP"));
+        std::fs::write(&image, carrier).expect("create JPEG carrier");
+        std::fs::remove_file(&embedded).expect("remove standalone embedded ZIP");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
+            .extract(&image)
+            .expect("extract JPEG carrier");
+
+        assert_eq!(outcome.output, payload);
+        assert_eq!(std::fs::read(&payload).unwrap(), b"JPEG hidden payload");
+        assert!(
+            !image.exists(),
+            "successful extraction must clean the carrier"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn carrier_with_several_appended_archives_commits_every_payload() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let first_payload = sandbox.path().join("first-hidden.txt");
+        let second_payload = sandbox.path().join("second-hidden.txt");
+        let first_embedded = sandbox.path().join("first-embedded.zip");
+        let second_embedded = sandbox.path().join("second-embedded.zip");
+        let video = sandbox.path().join("carrier.mp4");
+        std::fs::write(&first_payload, b"first hidden").expect("create first payload");
+        std::fs::write(&second_payload, b"second hidden").expect("create second payload");
+        create_zip_archive(
+            &seven_zip,
+            sandbox.path(),
+            &first_embedded,
+            "first-hidden.txt",
+        );
+        create_zip_archive(
+            &seven_zip,
+            sandbox.path(),
+            &second_embedded,
+            "second-hidden.txt",
+        );
+        std::fs::remove_file(&first_payload).expect("remove first source payload");
+        std::fs::remove_file(&second_payload).expect("remove second source payload");
+
+        let mut carrier = minimal_mp4();
+        carrier.extend(std::fs::read(&first_embedded).expect("read first embedded ZIP"));
+        carrier.extend(std::fs::read(&second_embedded).expect("read second embedded ZIP"));
+        std::fs::write(&video, carrier).expect("create carrier with two payloads");
+        std::fs::remove_file(&first_embedded).expect("remove first standalone ZIP");
+        std::fs::remove_file(&second_embedded).expect("remove second standalone ZIP");
+
+        let outcome = ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource)
+            .extract(&video)
+            .expect("extract every embedded archive");
+
+        assert_eq!(outcome.output, first_payload);
+        assert_eq!(outcome.additional_outputs, vec![second_payload.clone()]);
+        assert_eq!(std::fs::read(&first_payload).unwrap(), b"first hidden");
+        assert_eq!(std::fs::read(&second_payload).unwrap(), b"second hidden");
+        assert!(
+            !video.exists(),
+            "successful extraction must clean the carrier"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn ordinary_jpeg_is_rejected_without_modifying_the_source() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let image = sandbox.path().join("ordinary.jpg");
+        std::fs::copy(asset("whatever.jpg"), &image).expect("copy ordinary JPEG");
+
+        let result =
+            ExtractionWorkflow::with_source_cleaner(&seven_zip, RemoveSource).extract(&image);
+
+        assert_eq!(
+            result,
+            Err(ExtractionError::UnsupportedInput(image.clone()))
+        );
+        assert!(image.is_file(), "ordinary image must be preserved");
+        assert_eq!(
+            std::fs::read_dir(sandbox.path()).unwrap().count(),
+            1,
+            "ordinary image must not create output or leave a workspace"
+        );
+    }
+
     #[test]
     #[ignore = "requires cargo xtask prepare"]
     fn rar_non_first_volume_extracts_and_cleans_the_complete_set() {
@@ -1757,6 +4117,238 @@ mod tests {
         assert!(!final_volume.exists(), "final ZIP volume must be cleaned");
     }
 
+    #[test]
+    fn embedded_volume_sets_are_named_for_their_format() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let volume = |name: &str, archive_type: &str, index: u32| {
+            let path = sandbox.path().join(name);
+            std::fs::write(&path, name).unwrap();
+            (
+                path,
+                ArchiveProperties {
+                    archive_type: Some(archive_type.to_owned()),
+                    multivolume: true,
+                    volume_index: Some(index),
+                    ..Default::default()
+                },
+            )
+        };
+        let archives = vec![
+            volume("1.zip", "zip", 0),
+            volume("2.zip", "zip", 1),
+            volume("3.zip", "zip", 2),
+            volume("4.rar", "Rar5", 0),
+            volume("5.rar", "Rar5", 1),
+        ];
+
+        let inputs = group_embedded_volumes(sandbox.path(), archives).expect("group volumes");
+
+        assert_eq!(
+            inputs,
+            [
+                sandbox.path().join("volume-set-0.zip"),
+                sandbox.path().join("volume-set-1.part1.rar"),
+            ]
+        );
+        assert_eq!(
+            directory_names(sandbox.path()),
+            [
+                "volume-set-0.z01",
+                "volume-set-0.z02",
+                "volume-set-0.zip",
+                "volume-set-1.part1.rar",
+                "volume-set-1.part2.rar",
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(sandbox.path().join("volume-set-0.zip")).unwrap(),
+            "3.zip"
+        );
+        assert_eq!(embedded_volume_name("7z", 2, 0, 2), "volume-set-2.7z.001");
+    }
+
+    #[test]
+    fn failed_commit_rolls_back_outputs_already_committed() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let input = sandbox.path().join("bundle.exe");
+        let workspace = sandbox.path().join(".ezz-work-test");
+        let staged: Vec<_> = ["first", "second"]
+            .into_iter()
+            .map(|name| {
+                let extracted = workspace.join(name);
+                std::fs::create_dir_all(&extracted).unwrap();
+                std::fs::write(extracted.join(format!("{name}.txt")), name).unwrap();
+                stage_output(&input, &extracted, OsStr::new("bundle")).unwrap()
+            })
+            .collect();
+        std::fs::remove_file(&staged[1].source).unwrap();
+
+        let result = commit_outputs(&input, &staged);
+
+        assert!(
+            matches!(result, Err(ExtractionError::FileSystem { .. })),
+            "{result:?}"
+        );
+        assert!(
+            staged[0].source.is_file(),
+            "first output must be rolled back"
+        );
+        assert_eq!(
+            directory_names(sandbox.path()),
+            [OsString::from(".ezz-work-test")]
+        );
+    }
+
+    #[test]
+    fn rollback_restores_every_commit_it_can_and_reports_the_rest() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let workspace = sandbox.path().join(".ezz-work-rollback");
+        std::fs::create_dir(&workspace).unwrap();
+        let first = sandbox.path().join("first.txt");
+        let lost = sandbox.path().join("lost.txt");
+        let last = sandbox.path().join("last.txt");
+        std::fs::write(&first, "first").unwrap();
+        std::fs::write(&last, "last").unwrap();
+        let staged: Vec<_> = ["first.txt", "lost.txt", "last.txt"]
+            .into_iter()
+            .map(|name| StagedOutput {
+                source: workspace.join(name),
+                name: OsString::from(name),
+                folder: false,
+            })
+            .collect();
+
+        let failures = roll_back_commits(&staged, &[first, lost.clone(), last]).unwrap_err();
+
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("first.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("last.txt")).unwrap(),
+            "last"
+        );
+        assert!(failures.starts_with(&format!("could not restore {}", lost.display())));
+        assert!(!failures.contains(';'));
+    }
+
+    #[test]
+    fn commits_never_replace_an_existing_destination() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let folder = sandbox.path().join("folder");
+        let file = sandbox.path().join("file.txt");
+        std::fs::create_dir(&folder).unwrap();
+        std::fs::write(&file, "extracted").unwrap();
+        let empty = sandbox.path().join("empty");
+        let existing = sandbox.path().join("existing.txt");
+        std::fs::create_dir(&empty).unwrap();
+        std::fs::write(&existing, "kept").unwrap();
+
+        for (source, target) in [(&folder, &empty), (&file, &existing)] {
+            let error = rename_no_replace(source, target).expect_err("destination exists");
+            assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+            assert!(source.exists());
+        }
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "kept");
+        assert_eq!(directory_names(&empty), Vec::<OsString>::new());
+    }
+
+    #[test]
+    fn concurrent_commits_into_one_directory_never_share_a_destination() {
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let input = sandbox.path().join("release.zip");
+        let workspaces: Vec<_> = (0..8)
+            .map(|index| {
+                let extracted = sandbox.path().join(format!(".ezz-work-{index}"));
+                std::fs::create_dir(&extracted).unwrap();
+                std::fs::write(extracted.join("report.txt"), index.to_string()).unwrap();
+                extracted
+            })
+            .collect();
+
+        let mut outputs: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = workspaces
+                .iter()
+                .map(|extracted| {
+                    let input = &input;
+                    scope.spawn(move || {
+                        commit_output(input, extracted, OsStr::new("release")).unwrap()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        outputs.sort();
+        outputs.dedup();
+
+        assert_eq!(outputs.len(), 8, "every commit needs its own destination");
+        let mut contents: Vec<_> = outputs
+            .iter()
+            .map(|output| std::fs::read_to_string(output).unwrap())
+            .collect();
+        contents.sort();
+        assert_eq!(
+            contents,
+            (0..8).map(|index| index.to_string()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[ignore = "requires cargo xtask prepare"]
+    fn parallel_batch_serializes_password_prompts() {
+        let seven_zip = prepared_seven_zip();
+        assert!(
+            seven_zip.is_file(),
+            "run `cargo xtask prepare` before this test"
+        );
+
+        let sandbox = tempfile::tempdir().expect("create test sandbox");
+        let mut archives = Vec::new();
+        for index in 0..4 {
+            let payload = format!("secret-{index}.txt");
+            let archive = sandbox.path().join(format!("secret-{index}.7z"));
+            std::fs::write(sandbox.path().join(&payload), index.to_string()).unwrap();
+            create_encrypted_archive(
+                &seven_zip,
+                sandbox.path(),
+                &archive,
+                &payload,
+                "correct horse",
+            );
+            std::fs::remove_file(sandbox.path().join(&payload)).unwrap();
+            archives.push(archive);
+        }
+        let prompt = ScriptedPasswordPrompt::new((0..4).map(|_| PasswordResponse {
+            password: "correct horse".to_owned(),
+            remember: false,
+            keep_original: false,
+        }));
+        let workflow = ExtractionWorkflow::with_adapters(&seven_zip, RemoveSource, prompt);
+
+        let report = crate::DesktopApplication::new(workflow)
+            .with_concurrency(4)
+            .process_files(archives.clone());
+
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .map(|file| file.input.clone())
+                .collect::<Vec<_>>(),
+            archives
+        );
+        for (index, file) in report.files.iter().enumerate() {
+            let outcome = file.result.as_ref().expect("extract encrypted archive");
+            assert_eq!(
+                std::fs::read_to_string(&outcome.output).unwrap(),
+                index.to_string()
+            );
+        }
+    }
+
     fn create_archive(seven_zip: &Path, directory: &Path, archive: &Path, inputs: &[&str]) {
         let mut command = Command::new(seven_zip);
         command
@@ -1847,7 +4439,7 @@ mod tests {
 
     fn minimal_mkv() -> Vec<u8> {
         vec![
-            0x1a, 0x45, 0xdf, 0xa3, 0x8f, 0x42, 0x86, 0x81, 0x01, 0x42, 0xf7, 0x81, 0x01, 0x42,
+            0x1a, 0x45, 0xdf, 0xa3, 0x8c, 0x42, 0x86, 0x81, 0x01, 0x42, 0xf7, 0x81, 0x01, 0x42,
             0xf2, 0x81, 0x04,
         ]
     }
@@ -1873,6 +4465,15 @@ mod tests {
         assert!(status.success(), "7-Zip must create split test archive");
     }
 
+    fn directory_names(directory: &Path) -> Vec<OsString> {
+        let mut names: Vec<_> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        names
+    }
+
     fn prepared_seven_zip() -> PathBuf {
         let binary_name = if cfg!(target_os = "windows") {
             "7zz.exe"
@@ -1892,4 +4493,10 @@ mod tests {
             .join("fixtures")
             .join(name)
     }
+
+    fn asset(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join(name)
+    }
 }
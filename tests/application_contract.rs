@@ -1,4 +1,8 @@
-use ezz::{DesktopApplication, ExtractionError, ExtractionWorkflow, FileOutcome};
+use ezz::{
+    ArchiveCreationError, ArchiveCreationWorkflow, CreatedArchive, DesktopApplication,
+    DirectoryExpansion, DirectoryHandling, ExpandedDirectory, ExtractionError, ExtractionWorkflow,
+    FileOutcome,
+};
 
 #[test]
 fn every_input_produces_an_outcome_in_original_order() {
@@ -23,14 +27,181 @@ fn every_input_produces_an_outcome_in_original_order() {
                 input: first_missing.clone(),
                 result: Err(ExtractionError::InputNotFound(first_missing)),
             },
-            FileOutcome {
-                input: directory.clone(),
-                result: Err(ExtractionError::InputNotFile(directory)),
-            },
             FileOutcome {
                 input: second_missing.clone(),
                 result: Err(ExtractionError::InputNotFound(second_missing)),
             },
         ]
     );
+    assert_eq!(
+        report.expanded_directories,
+        vec![DirectoryExpansion {
+            directory,
+            result: Ok(ExpandedDirectory {
+                archives: Vec::new(),
+                skipped: Vec::new(),
+            }),
+        }]
+    );
+}
+
+#[test]
+fn directory_expansion_fails_once_when_the_engine_is_missing() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let directory = sandbox.path().join("downloads");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::create_dir(&directory).expect("create directory input");
+    std::fs::write(directory.join("release.zip"), b"not really a zip").expect("write file");
+
+    let workflow = ExtractionWorkflow::new(seven_zip.clone());
+    let report = DesktopApplication::new(workflow).process_files([directory.clone()]);
+
+    assert!(report.files.is_empty());
+    assert_eq!(
+        report.expanded_directories,
+        vec![DirectoryExpansion {
+            directory,
+            result: Err(ExtractionError::EngineNotFound(seven_zip)),
+        }]
+    );
+}
+
+#[test]
+fn directory_inputs_are_archived_when_directory_handling_creates_archives() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let directory = sandbox.path().join("folder");
+    let missing = sandbox.path().join("missing.zip");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::create_dir(&directory).expect("create directory input");
+
+    let report = DesktopApplication::new(ExtractionWorkflow::new(&seven_zip))
+        .with_directory_handling(DirectoryHandling::CreateArchive(
+            ArchiveCreationWorkflow::new(&seven_zip),
+        ))
+        .process_files([directory.clone(), missing.clone()]);
+
+    assert_eq!(
+        report.files,
+        vec![FileOutcome {
+            input: missing.clone(),
+            result: Err(ExtractionError::InputNotFound(missing)),
+        }]
+    );
+    assert_eq!(
+        report.created_archives,
+        vec![CreatedArchive {
+            input: directory,
+            result: Err(ArchiveCreationError::EngineNotFound(seven_zip)),
+        }]
+    );
+}
+
+#[test]
+fn parallel_batch_reports_outcomes_in_original_order() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let inputs: Vec<_> = (0..6)
+        .map(|index| sandbox.path().join(format!("missing-{index}.zip")))
+        .collect();
+
+    let workflow = ExtractionWorkflow::new(sandbox.path().join("7zz"));
+    let report = DesktopApplication::new(workflow)
+        .with_concurrency(3)
+        .process_files(inputs.clone());
+
+    assert_eq!(
+        report.files,
+        inputs
+            .into_iter()
+            .map(|input| FileOutcome {
+                input: input.clone(),
+                result: Err(ExtractionError::InputNotFound(input)),
+            })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn every_header_encrypted_archive_in_a_directory_is_queued() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::write(
+        &seven_zip,
+        "#!/bin/sh\n\
+         echo 'ERROR: Can not open encrypted archive. Wrong password?' >&2\n\
+         exit 2\n",
+    )
+    .expect("write fake 7-Zip");
+    std::fs::set_permissions(&seven_zip, std::fs::Permissions::from_mode(0o755))
+        .expect("make fake 7-Zip executable");
+    let directory = sandbox.path().join("downloads");
+    std::fs::create_dir(&directory).expect("create directory input");
+    let archives = ["first.7z", "second.7z"].map(|name| directory.join(name));
+    for archive in &archives {
+        std::fs::write(archive, b"7z\xbc\xaf\x27\x1c").expect("write archive");
+    }
+
+    let report = DesktopApplication::new(ExtractionWorkflow::new(&seven_zip))
+        .process_files([directory.clone()]);
+
+    assert_eq!(
+        report.expanded_directories,
+        vec![DirectoryExpansion {
+            directory,
+            result: Ok(ExpandedDirectory {
+                archives: archives.to_vec(),
+                skipped: Vec::new(),
+            }),
+        }]
+    );
+    assert_eq!(
+        report
+            .files
+            .iter()
+            .map(|outcome| &outcome.input)
+            .collect::<Vec<_>>(),
+        archives.iter().collect::<Vec<_>>()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn volumes_in_a_relative_directory_are_queued_once() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Component, PathBuf};
+
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::write(&seven_zip, "#!/bin/sh\nexit 0\n").expect("write fake 7-Zip");
+    std::fs::set_permissions(&seven_zip, std::fs::Permissions::from_mode(0o755))
+        .expect("make fake 7-Zip executable");
+    let downloads = sandbox.path().join("downloads");
+    std::fs::create_dir(&downloads).expect("create directory input");
+    for name in ["release.7z.001", "release.7z.002"] {
+        std::fs::write(downloads.join(name), b"7z\xbc\xaf\x27\x1c").expect("write volume");
+    }
+    let current = std::env::current_dir().expect("read current directory");
+    let mut directory: PathBuf = current
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .map(|_| "..")
+        .collect();
+    directory.push(downloads.strip_prefix("/").expect("absolute sandbox"));
+
+    let report = DesktopApplication::new(ExtractionWorkflow::new(&seven_zip))
+        .process_files([directory.clone()]);
+
+    assert_eq!(
+        report.expanded_directories,
+        vec![DirectoryExpansion {
+            directory: directory.clone(),
+            result: Ok(ExpandedDirectory {
+                archives: vec![current.join(&directory).join("release.7z.001")],
+                skipped: Vec::new(),
+            }),
+        }]
+    );
+    assert_eq!(report.files.len(), 1);
 }
@@ -0,0 +1,67 @@
+use ezz::{ArchiveCreationError, ArchiveCreationOptions, ArchiveCreationWorkflow, ArchiveFormat};
+
+#[test]
+fn missing_input_is_rejected_with_its_original_path() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let missing = sandbox.path().join("missing");
+    let seven_zip = sandbox.path().join("7zz");
+
+    let result = ArchiveCreationWorkflow::new(&seven_zip).create([&missing]);
+
+    assert_eq!(result, Err(ArchiveCreationError::InputNotFound(missing)));
+}
+
+#[test]
+fn inputs_from_different_directories_are_rejected() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let first = sandbox.path().join("first.txt");
+    let nested = sandbox.path().join("nested");
+    let second = nested.join("second.txt");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::create_dir(&nested).expect("create nested directory");
+    std::fs::write(&first, b"first").expect("create first input");
+    std::fs::write(&second, b"second").expect("create second input");
+
+    let result = ArchiveCreationWorkflow::new(&seven_zip).create([&first, &second]);
+
+    assert_eq!(result, Err(ArchiveCreationError::MixedParents(second)));
+}
+
+#[test]
+fn tar_zstd_rejects_a_password_before_starting_7zip() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let input = sandbox.path().join("folder");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::create_dir(&input).expect("create directory input");
+
+    let result = ArchiveCreationWorkflow::with_options(
+        &seven_zip,
+        ArchiveCreationOptions {
+            format: ArchiveFormat::TarZstd,
+            password: Some("secret".to_owned()),
+            ..ArchiveCreationOptions::default()
+        },
+    )
+    .create([&input]);
+
+    assert_eq!(
+        result,
+        Err(ArchiveCreationError::UnsupportedOption {
+            format: "tar.zst",
+            option: "passwords",
+        })
+    );
+    assert_eq!(std::fs::read_dir(sandbox.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn missing_seven_zip_is_reported_for_an_existing_input() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let input = sandbox.path().join("folder");
+    let seven_zip = sandbox.path().join("7zz");
+    std::fs::create_dir(&input).expect("create directory input");
+
+    let result = ArchiveCreationWorkflow::new(&seven_zip).create([&input]);
+
+    assert_eq!(result, Err(ArchiveCreationError::EngineNotFound(seven_zip)));
+}
@@ -0,0 +1,87 @@
+use ezz::{PasswordDatabaseError, PasswordDatabaseKey, PasswordManager, StoredPassword};
+
+#[test]
+fn imported_passwords_can_be_pinned_labelled_removed_and_exported() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let database = sandbox.path().join("passwords.json");
+    let manager = PasswordManager::new(&database);
+
+    assert_eq!(manager.list(), Ok(Vec::new()));
+    assert_eq!(manager.import("first\r\n\nsecond\nfirst\nthird\n"), Ok(3));
+    assert_eq!(manager.import("second\nfourth"), Ok(1));
+    assert_eq!(manager.set_pinned("third", true), Ok(true));
+    assert_eq!(manager.set_label("third", Some(" team share ")), Ok(true));
+    assert_eq!(manager.remove("second"), Ok(true));
+    assert_eq!(manager.remove("missing"), Ok(false));
+    assert_eq!(manager.set_pinned("missing", true), Ok(false));
+
+    let listed = PasswordManager::new(&database).list().unwrap();
+    assert_eq!(
+        listed[0],
+        StoredPassword {
+            password: "third".to_owned(),
+            label: Some("team share".to_owned()),
+            uses: 0,
+            last_used: None,
+            failures: 0,
+            pinned: true,
+        }
+    );
+    assert_eq!(manager.export(), Ok("third\nfirst\nfourth\n".to_owned()));
+}
+
+#[test]
+fn unreadable_database_is_reported_with_its_path() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let database = sandbox.path().join("passwords.json");
+    std::fs::write(&database, b"{\"version\": 99}").expect("write future database");
+
+    assert_eq!(
+        PasswordManager::new(&database).import("secret"),
+        Err(PasswordDatabaseError::Failed {
+            path: database,
+            message: "unsupported password database version 99".to_owned(),
+        })
+    );
+}
+
+#[test]
+fn v2_password_file_is_imported_once_without_losing_usage() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let database = sandbox.path().join("passwords.json");
+    let legacy = sandbox.path().join(".ezz.pw");
+    std::fs::write(&legacy, "0 0 0\n7 legacy\n2 shared\n").expect("write v2 database");
+    let manager = PasswordManager::new(&database);
+    manager.import("shared").unwrap();
+
+    assert_eq!(manager.import_v2(&legacy), Ok(1));
+    assert_eq!(manager.import_v2(&legacy), Ok(0));
+    let uses: Vec<_> = manager
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|stored| (stored.password, stored.uses))
+        .collect();
+    assert_eq!(uses, [("legacy".to_owned(), 7), ("shared".to_owned(), 2)]);
+    assert!(matches!(
+        manager.import_v2(&sandbox.path().join("missing.pw")),
+        Err(PasswordDatabaseError::Failed { .. })
+    ));
+}
+
+#[test]
+fn encrypted_databases_are_reported_as_encrypted() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let database = sandbox.path().join("passwords.json");
+    let key_file = sandbox.path().join("passwords.key");
+    std::fs::write(&key_file, b"key file contents").expect("write key file");
+    let plaintext = PasswordManager::new(&database);
+    plaintext.import("secret").unwrap();
+    assert_eq!(plaintext.is_encrypted(), Ok(false));
+
+    let keyed = PasswordManager::new(&database).with_key(PasswordDatabaseKey::KeyFile(key_file));
+    assert_eq!(keyed.is_encrypted(), Ok(true));
+    keyed.encrypt().unwrap();
+
+    assert_eq!(PasswordManager::new(&database).is_encrypted(), Ok(true));
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use ezz::{FolderWatcher, WatchError};
+
+#[test]
+fn missing_directory_cannot_be_watched() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let missing = sandbox.path().join("downloads");
+
+    let result = FolderWatcher::new([missing.clone()], false);
+
+    assert_eq!(result.err(), Some(WatchError::DirectoryNotFound(missing)));
+}
+
+#[test]
+fn completed_download_is_released_after_it_settles() {
+    let sandbox = tempfile::tempdir().expect("create test sandbox");
+    let mut watcher = FolderWatcher::new([sandbox.path()], false)
+        .expect("watch sandbox")
+        .with_settle_time(Duration::from_millis(200));
+    let partial = sandbox.path().join("release.zip.crdownload");
+    let completed = sandbox.path().join("release.zip");
+
+    std::fs::write(&partial, b"PK\x03\x04").expect("write partial download");
+    std::fs::rename(&partial, &completed).expect("complete download");
+
+    assert_eq!(watcher.next_batch(), Ok(vec![completed]));
+}